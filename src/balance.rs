@@ -0,0 +1,125 @@
+use anyhow::{Result, anyhow, bail};
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::pubkey::Pubkey;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use tokio::time::Duration;
+use tracing::warn;
+
+/// 缓存的 input_mint 余额
+///
+/// 启动时读取一次，之后由后台任务定期刷新，每次套利只读取缓存，不请求 RPC。
+/// 刷新失败时保留上一次的值
+pub struct InputBalance {
+    rpc_client: Arc<RpcClient>,
+    user: Pubkey,
+    mint: Pubkey,
+    balance: AtomicU64,
+}
+
+impl InputBalance {
+    pub async fn start(
+        rpc_client: Arc<RpcClient>,
+        user: Pubkey,
+        mint: Pubkey,
+        interval: Duration,
+    ) -> Result<Arc<Self>> {
+        let handle = Arc::new(Self {
+            rpc_client,
+            user,
+            mint,
+            balance: AtomicU64::new(0),
+        });
+        handle.refresh().await?;
+
+        let refresher = Arc::downgrade(&handle);
+        tokio::spawn(async move {
+            loop {
+                tokio::time::sleep(interval).await;
+                // 所有持有者都已释放时停止刷新
+                let Some(handle) = refresher.upgrade() else {
+                    break;
+                };
+                if let Err(e) = handle.refresh().await {
+                    warn!("刷新 {} 余额失败: {}", handle.mint, e);
+                }
+            }
+        });
+
+        Ok(handle)
+    }
+
+    /// 最近一次读取的余额
+    pub fn get(&self) -> u64 {
+        self.balance.load(Ordering::Relaxed)
+    }
+
+    async fn refresh(&self) -> Result<()> {
+        let balance = get_input_token_balance(&self.rpc_client, &self.user, &self.mint).await?;
+        self.balance.store(balance, Ordering::Relaxed);
+        Ok(())
+    }
+}
+
+/// 闪电贷只支持使用 token 账户(SOL 为 wSOL 账户)中的余额作为本金
+///
+/// wrap_and_unwrap_sol = true 时 Jupiter 从原生 SOL 转入完整的 in_amount 进行包装，
+/// 并在兑换结束后关闭 wSOL 账户，借入的资金无法用于兑换，还款时账户也已不存在
+pub fn check_wrap_and_unwrap_sol(wrap_and_unwrap_sol: bool) -> Result<()> {
+    if wrap_and_unwrap_sol {
+        bail!("闪电贷不支持 wrap_and_unwrap_sol = true，请使用 wSOL 作为本金并设置为 false");
+    }
+    Ok(())
+}
+
+/// 可用作本金的 input_mint 余额，即钱包中 input_mint token 账户的余额
+pub async fn get_input_token_balance(
+    rpc_client: &Arc<RpcClient>,
+    user_pubkey: &Pubkey,
+    input_mint: &Pubkey,
+) -> Result<u64> {
+    let associated_token_address =
+        spl_associated_token_account::get_associated_token_address(user_pubkey, input_mint);
+    get_token_account_balance(rpc_client, &associated_token_address).await
+}
+
+/// 读取 token 账户余额
+///
+/// 只有账户不存在时余额为 0，RPC 超时、限流等错误直接返回，避免误判为余额不足
+pub async fn get_token_account_balance(rpc_client: &RpcClient, account: &Pubkey) -> Result<u64> {
+    let response = rpc_client
+        .get_account_with_commitment(account, rpc_client.commitment())
+        .await
+        .map_err(|e| anyhow!("获取 token 账户 {} 失败: {}", account, e))?;
+    match response.value {
+        None => Ok(0),
+        Some(data) => {
+            token_amount(&data.data).ok_or_else(|| anyhow!("{} 不是 token 账户", account))
+        }
+    }
+}
+
+/// SPL Token 账户余额，位于账户数据第 64..72 字节
+fn token_amount(data: &[u8]) -> Option<u64> {
+    let amount = data.get(64..72)?;
+    Some(u64::from_le_bytes(amount.try_into().ok()?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_token_amount() {
+        let mut data = vec![0u8; 165];
+        data[64..72].copy_from_slice(&1_500_000u64.to_le_bytes());
+        assert_eq!(token_amount(&data), Some(1_500_000));
+        assert_eq!(token_amount(&data[..64]), None);
+    }
+
+    #[test]
+    fn test_check_wrap_and_unwrap_sol() {
+        assert!(check_wrap_and_unwrap_sol(false).is_ok());
+        assert!(check_wrap_and_unwrap_sol(true).is_err());
+    }
+}
@@ -1,42 +1,229 @@
-use solana_client::nonblocking::rpc_client::RpcClient;
-use solana_sdk::commitment_config::CommitmentConfig;
-use std::sync::Arc;
+use anyhow::{Result, anyhow};
+use futures_util::StreamExt;
+use serde_json::json;
+use solana_client::{
+    nonblocking::{pubsub_client::PubsubClient, rpc_client::RpcClient},
+    rpc_request::RpcRequest,
+    rpc_response::{Response, RpcBlockhash},
+};
+use solana_sdk::{commitment_config::CommitmentConfig, hash::Hash};
+use std::collections::VecDeque;
+use std::str::FromStr;
 use std::sync::atomic::{AtomicU64, Ordering};
-use tokio::{sync::RwLock, time::Duration};
+use std::sync::{Arc, Mutex};
+use tokio::{
+    sync::{RwLock, watch},
+    time::{Duration, Instant},
+};
+use tracing::{debug, error, info, warn};
 
-const INTERVAL: Duration = Duration::from_secs(2);
+/// 保留最近获取的 blockhash 数量，用于查询已签名交易的 last_valid_block_height
+const RECENT_BLOCKHASHES: usize = 64;
+/// 超过该时间未收到 slot 通知视为订阅断开
+const SLOT_NOTIFICATION_TIMEOUT: Duration = Duration::from_secs(5);
+/// 订阅断开后改为轮询，每隔该时间尝试重新订阅
+const RESUBSCRIBE_INTERVAL: Duration = Duration::from_secs(30);
+
+/// 跟踪最新的 blockhash 及 slot
+///
+/// 通过 slotSubscribe 在每个新 slot 获取 blockhash，WebSocket 不可用时改为定期轮询
 pub struct LatestBlockhash {
-    blockhash: RwLock<solana_sdk::hash::Hash>,
+    /// 最近获取的 (blockhash, last_valid_block_height)，最新的在队尾
+    blockhashes: RwLock<VecDeque<(Hash, u64)>>,
     slot: AtomicU64,
+    /// 最近一次成功获取 blockhash 的时间
+    updated_at: Mutex<Option<Instant>>,
+    shutdown: watch::Sender<bool>,
 }
 
 impl LatestBlockhash {
-    pub async fn start(rpc_client: Arc<RpcClient>) -> Arc<Self> {
-        let latest_blockhash = Arc::new(LatestBlockhash {
-            blockhash: RwLock::new(solana_sdk::hash::Hash::default()),
+    fn new() -> Self {
+        Self {
+            blockhashes: RwLock::new(VecDeque::with_capacity(RECENT_BLOCKHASHES)),
             slot: AtomicU64::new(0),
-        });
+            updated_at: Mutex::new(None),
+            shutdown: watch::Sender::new(false),
+        }
+    }
 
-        let latest_blockhash_clone = Arc::clone(&latest_blockhash);
+    /// websocket_url 为空时只轮询
+    pub async fn start(
+        rpc_client: Arc<RpcClient>,
+        websocket_url: Option<String>,
+        poll_interval: Duration,
+    ) -> Arc<Self> {
+        let latest_blockhash = Arc::new(Self::new());
+        latest_blockhash.refresh(&rpc_client).await;
+
+        let tracker = Arc::clone(&latest_blockhash);
         tokio::spawn(async move {
-            loop {
-                if let Ok((blockhash, slot)) = rpc_client
-                    .get_latest_blockhash_with_commitment(CommitmentConfig::confirmed())
-                    .await
-                {
-                    let mut blockhash_write = latest_blockhash_clone.blockhash.write().await;
-                    *blockhash_write = blockhash;
-                    latest_blockhash_clone.slot.store(slot, Ordering::Relaxed);
-                }
-                tokio::time::sleep(INTERVAL).await;
-            }
+            tracker
+                .run(&rpc_client, websocket_url.as_deref(), poll_interval)
+                .await;
+            info!("blockhash 跟踪已停止");
         });
 
         latest_blockhash
     }
 
-    pub async fn get_blockhash(&self) -> solana_sdk::hash::Hash {
-        self.blockhash.read().await.clone()
+    /// 停止后台任务
+    pub fn shutdown(&self) {
+        self.shutdown.send_replace(true);
+    }
+
+    async fn run(
+        &self,
+        rpc_client: &RpcClient,
+        websocket_url: Option<&str>,
+        poll_interval: Duration,
+    ) {
+        let mut shutdown = self.shutdown.subscribe();
+        loop {
+            let poll_for = match websocket_url {
+                Some(url) => {
+                    match self.subscribe(rpc_client, url, &mut shutdown).await {
+                        Ok(()) => return,
+                        Err(e) => error!("slot 订阅失败，改为轮询 blockhash: {}", e),
+                    }
+                    Some(RESUBSCRIBE_INTERVAL)
+                }
+                None => None,
+            };
+
+            // 轮询，配置了 WebSocket 时到期后重新订阅
+            let deadline = poll_for.map(|d| Instant::now() + d);
+            while deadline.is_none_or(|deadline| Instant::now() < deadline) {
+                tokio::select! {
+                    _ = shutdown.changed() => return,
+                    _ = tokio::time::sleep(poll_interval) => self.refresh(rpc_client).await,
+                }
+            }
+        }
+    }
+
+    /// 每收到一个新 slot 获取一次 blockhash，收到停止信号时返回 Ok
+    async fn subscribe(
+        &self,
+        rpc_client: &RpcClient,
+        websocket_url: &str,
+        shutdown: &mut watch::Receiver<bool>,
+    ) -> Result<()> {
+        let pubsub_client = PubsubClient::new(websocket_url).await?;
+        let (mut slots, unsubscribe) = pubsub_client.slot_subscribe().await?;
+        info!("已订阅 slot: {}", websocket_url);
+
+        let result = loop {
+            tokio::select! {
+                _ = shutdown.changed() => break Ok(()),
+                notification = tokio::time::timeout(SLOT_NOTIFICATION_TIMEOUT, slots.next()) => {
+                    match notification {
+                        Ok(Some(info)) => {
+                            self.slot.fetch_max(info.slot, Ordering::Relaxed);
+                            self.refresh(rpc_client).await;
+                        }
+                        Ok(None) => break Err(anyhow!("订阅已断开")),
+                        Err(_) => {
+                            break Err(anyhow!(
+                                "{:?} 内未收到 slot 通知",
+                                SLOT_NOTIFICATION_TIMEOUT
+                            ));
+                        }
+                    }
+                }
+            }
+        };
+
+        unsubscribe().await;
+        drop(slots);
+        _ = pubsub_client.shutdown().await;
+        result
+    }
+
+    async fn refresh(&self, rpc_client: &RpcClient) {
+        // get_latest_blockhash_with_commitment 只返回 last_valid_block_height，
+        // slot 需要从响应的 context 中获取
+        let res = match rpc_client
+            .send::<Response<RpcBlockhash>>(
+                RpcRequest::GetLatestBlockhash,
+                json!([CommitmentConfig::confirmed()]),
+            )
+            .await
+        {
+            Ok(res) => res,
+            Err(e) => {
+                warn!("获取 blockhash 失败: {}", e);
+                return;
+            }
+        };
+
+        match Hash::from_str(&res.value.blockhash) {
+            Ok(blockhash) => {
+                self.push(blockhash, res.value.last_valid_block_height)
+                    .await;
+                self.slot.fetch_max(res.context.slot, Ordering::Relaxed);
+                *self.updated_at.lock().unwrap() = Some(Instant::now());
+                debug!("blockhash: {}, slot: {}", blockhash, res.context.slot);
+            }
+            Err(e) => warn!("blockhash {} 解析失败: {}", res.value.blockhash, e),
+        }
+    }
+
+    async fn push(&self, blockhash: Hash, last_valid_block_height: u64) {
+        let mut blockhashes = self.blockhashes.write().await;
+        if blockhashes
+            .back()
+            .is_some_and(|(last, _)| *last == blockhash)
+        {
+            return;
+        }
+        if blockhashes.len() == RECENT_BLOCKHASHES {
+            blockhashes.pop_front();
+        }
+        blockhashes.push_back((blockhash, last_valid_block_height));
+    }
+
+    pub async fn get_blockhash(&self) -> Hash {
+        self.blockhashes
+            .read()
+            .await
+            .back()
+            .map(|(blockhash, _)| *blockhash)
+            .unwrap_or_default()
+    }
+
+    /// 获取 blockhash，距离上次成功更新超过 max_age 时返回错误，max_age 为 0 时不检查
+    pub async fn get_fresh_blockhash(&self, max_age: Duration) -> Result<Hash> {
+        match self.age() {
+            None => Err(anyhow!("尚未获取到 blockhash")),
+            Some(age) if !max_age.is_zero() && age > max_age => {
+                Err(anyhow!("blockhash 已 {:.2?} 未更新，拒绝签名", age))
+            }
+            Some(_) => Ok(self.get_blockhash().await),
+        }
+    }
+
+    /// 最近获取过的 blockhash 的 last_valid_block_height，区块高度超过该值后使用此 blockhash 的交易失效
+    pub async fn last_valid_block_height(&self, blockhash: &Hash) -> Option<u64> {
+        self.blockhashes
+            .read()
+            .await
+            .iter()
+            .rev()
+            .find(|(hash, _)| hash == blockhash)
+            .map(|(_, height)| *height)
+    }
+
+    /// 当前 slot，订阅 slot 时为最新通知的 slot，否则为最近一次获取 blockhash 时的 slot
+    pub fn slot(&self) -> u64 {
+        self.slot.load(Ordering::Relaxed)
+    }
+
+    /// 距离上次成功获取 blockhash 的时间，尚未获取到时返回 None
+    pub fn age(&self) -> Option<Duration> {
+        self.updated_at
+            .lock()
+            .unwrap()
+            .map(|updated_at| updated_at.elapsed())
     }
 }
 
@@ -49,7 +236,8 @@ mod tests {
         let rpc = RpcClient::new("https://api.mainnet-beta.solana.com".to_string());
         let rpc_client = Arc::new(rpc);
 
-        let latest_blockhash = LatestBlockhash::start(rpc_client.clone()).await;
+        let latest_blockhash =
+            LatestBlockhash::start(rpc_client.clone(), None, Duration::from_secs(2)).await;
 
         // 等待一下让它拉到 blockhash
         tokio::time::sleep(Duration::from_secs(4)).await;
@@ -61,5 +249,68 @@ mod tests {
         assert_ne!(hash, solana_sdk::hash::Hash::default());
         let hash2 = latest_blockhash.get_blockhash().await;
         assert_eq!(hash, hash2);
+        assert!(latest_blockhash.slot() > 0);
+
+        latest_blockhash.shutdown();
+    }
+
+    #[tokio::test]
+    async fn test_last_valid_block_height() {
+        let latest_blockhash = LatestBlockhash::new();
+        assert_eq!(latest_blockhash.get_blockhash().await, Hash::default());
+
+        let first = Hash::new_unique();
+        latest_blockhash.push(first, 100).await;
+        for height in 0..RECENT_BLOCKHASHES as u64 - 1 {
+            latest_blockhash
+                .push(Hash::new_unique(), 200 + height)
+                .await;
+        }
+        assert_eq!(
+            latest_blockhash.last_valid_block_height(&first).await,
+            Some(100)
+        );
+
+        // 超出保留数量后最早的 blockhash 被移除
+        let last = Hash::new_unique();
+        latest_blockhash.push(last, 300).await;
+        latest_blockhash.push(last, 300).await;
+        assert_eq!(latest_blockhash.get_blockhash().await, last);
+        assert_eq!(latest_blockhash.last_valid_block_height(&first).await, None);
+        assert_eq!(
+            latest_blockhash.last_valid_block_height(&last).await,
+            Some(300)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_get_fresh_blockhash() {
+        let latest_blockhash = LatestBlockhash::new();
+        assert!(latest_blockhash.age().is_none());
+        assert!(
+            latest_blockhash
+                .get_fresh_blockhash(Duration::ZERO)
+                .await
+                .is_err()
+        );
+
+        let hash = Hash::new_unique();
+        latest_blockhash.push(hash, 100).await;
+        *latest_blockhash.updated_at.lock().unwrap() =
+            Some(Instant::now() - Duration::from_secs(30));
+
+        assert!(
+            latest_blockhash
+                .get_fresh_blockhash(Duration::from_secs(10))
+                .await
+                .is_err()
+        );
+        assert_eq!(
+            latest_blockhash
+                .get_fresh_blockhash(Duration::ZERO)
+                .await
+                .unwrap(),
+            hash
+        );
     }
 }
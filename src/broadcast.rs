@@ -0,0 +1,457 @@
+use crate::config::BroadcastConfig;
+use crate::confirmation::{ConfirmationEvent, Outcome};
+use anyhow::{Result, anyhow};
+use futures_util::future::join_all;
+use solana_client::{nonblocking::rpc_client::RpcClient, rpc_config::RpcSendTransactionConfig};
+use solana_sdk::{
+    commitment_config::CommitmentConfig, signature::Signature, transaction::VersionedTransaction,
+};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use tokio::sync::mpsc;
+use tokio::time::{Duration, Instant};
+use tracing::{debug, info, warn};
+
+/// 不知道 last_valid_block_height 的交易(如使用持久 nonce)等待确认结果的最长时间
+const PENDING_MAX_AGE: Duration = Duration::from_secs(120);
+
+/// 单个发送端点的统计信息
+#[derive(Debug, Default)]
+pub struct EndpointStats {
+    sent: AtomicU64,
+    failed: AtomicU64,
+    /// 最先返回签名的次数
+    first: AtomicU64,
+    /// 最先返回签名且交易最终上链的次数
+    landed: AtomicU64,
+    /// 累计发送延时(ms)
+    total_latency_ms: AtomicU64,
+}
+
+impl EndpointStats {
+    fn reset(&self) {
+        self.sent.store(0, Ordering::Relaxed);
+        self.failed.store(0, Ordering::Relaxed);
+        self.total_latency_ms.store(0, Ordering::Relaxed);
+    }
+
+    fn avg_latency_ms(&self) -> Option<u64> {
+        let sent = self.sent.load(Ordering::Relaxed);
+        (sent > 0).then(|| self.total_latency_ms.load(Ordering::Relaxed) / sent)
+    }
+
+    fn error_rate(&self) -> f64 {
+        let sent = self.sent.load(Ordering::Relaxed);
+        if sent == 0 {
+            0.0
+        } else {
+            self.failed.load(Ordering::Relaxed) as f64 / sent as f64
+        }
+    }
+}
+
+/// 交易发送端点，如质押 RPC、SWQoS 服务商
+pub struct Endpoint {
+    pub url: String,
+    client: RpcClient,
+    pub stats: EndpointStats,
+    /// 因延时过高或失败率过高被暂停，到期后恢复
+    disabled_until: Mutex<Option<Instant>>,
+}
+
+impl Endpoint {
+    fn is_available(&self) -> bool {
+        let mut disabled_until = self.disabled_until.lock().unwrap();
+        match *disabled_until {
+            Some(until) if Instant::now() < until => false,
+            Some(_) => {
+                // 暂停到期，清空统计重新开始
+                *disabled_until = None;
+                self.stats.reset();
+                info!("发送端点 {} 已恢复", self.url);
+                true
+            }
+            None => true,
+        }
+    }
+}
+
+/// 端点统计快照，用于日志输出
+#[derive(Debug, Clone)]
+pub struct EndpointSnapshot {
+    pub url: String,
+    pub available: bool,
+    pub sent: u64,
+    pub failed: u64,
+    pub first: u64,
+    pub landed: u64,
+    pub avg_latency_ms: Option<u64>,
+}
+
+/// 广播结果
+#[derive(Debug, Clone)]
+pub struct BroadcastReceipt {
+    pub signature: Signature,
+    /// 最先返回签名的端点
+    pub first_endpoint: Arc<Endpoint>,
+}
+
+impl std::fmt::Debug for Endpoint {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Endpoint").field("url", &self.url).finish()
+    }
+}
+
+/// 等待确认结果的交易
+struct Pending {
+    /// 最先返回签名的端点
+    endpoint: Arc<Endpoint>,
+    last_valid_block_height: Option<u64>,
+    since: Instant,
+}
+
+impl Pending {
+    fn is_expired(&self, block_height: Option<u64>) -> bool {
+        match (self.last_valid_block_height, block_height) {
+            (Some(last_valid), Some(height)) => height > last_valid,
+            _ => self.since.elapsed() > PENDING_MAX_AGE,
+        }
+    }
+}
+
+/// 将同一笔已签名交易并发发送到多个端点
+pub struct Broadcaster {
+    endpoints: Vec<Arc<Endpoint>>,
+    config: BroadcastConfig,
+    /// 等待确认结果的交易，得出结果或 blockhash 过期后移除
+    pending: Mutex<HashMap<Signature, Pending>>,
+}
+
+impl Broadcaster {
+    /// 未配置发送端点时返回 None
+    pub fn new(config: &BroadcastConfig) -> Option<Arc<Self>> {
+        if config.endpoints.is_empty() {
+            return None;
+        }
+
+        let endpoints = config
+            .endpoints
+            .iter()
+            .map(|url| {
+                Arc::new(Endpoint {
+                    url: url.clone(),
+                    client: RpcClient::new_with_commitment(
+                        url.clone(),
+                        CommitmentConfig::confirmed(),
+                    ),
+                    stats: EndpointStats::default(),
+                    disabled_until: Mutex::new(None),
+                })
+            })
+            .collect();
+
+        Some(Arc::new(Self {
+            endpoints,
+            config: config.clone(),
+            pending: Mutex::new(HashMap::new()),
+        }))
+    }
+
+    pub fn endpoints(&self) -> &[Arc<Endpoint>] {
+        &self.endpoints
+    }
+
+    /// 并发发送到所有可用端点，返回最先成功的端点
+    ///
+    /// last_valid_block_height 为交易 blockhash 的有效期，区块高度超过后不再等待确认结果
+    pub async fn broadcast(
+        &self,
+        tx: &VersionedTransaction,
+        send_config: RpcSendTransactionConfig,
+        last_valid_block_height: Option<u64>,
+    ) -> Result<BroadcastReceipt> {
+        let targets = self.targets();
+        let (result_tx, mut result_rx) = mpsc::channel(targets.len());
+        for endpoint in targets {
+            let result_tx = result_tx.clone();
+            let tx = tx.clone();
+            let policy = self.config.clone();
+            tokio::spawn(async move {
+                let start = Instant::now();
+                let result = endpoint
+                    .client
+                    .send_transaction_with_config(&tx, send_config)
+                    .await;
+                let elapsed = start.elapsed();
+
+                endpoint.stats.sent.fetch_add(1, Ordering::Relaxed);
+                endpoint
+                    .stats
+                    .total_latency_ms
+                    .fetch_add(elapsed.as_millis() as u64, Ordering::Relaxed);
+                if result.is_err() {
+                    endpoint.stats.failed.fetch_add(1, Ordering::Relaxed);
+                }
+                debug!(
+                    "发送端点 {} 耗时 {:.4?}, 结果: {:?}",
+                    endpoint.url, elapsed, result
+                );
+                prune(&endpoint, &policy);
+
+                _ = result_tx
+                    .send((endpoint, result.map_err(|e| anyhow!("{}", e))))
+                    .await;
+            });
+        }
+        drop(result_tx);
+
+        let mut errors = Vec::new();
+        while let Some((endpoint, result)) = result_rx.recv().await {
+            match result {
+                Ok(signature) => {
+                    endpoint.stats.first.fetch_add(1, Ordering::Relaxed);
+                    self.pending.lock().unwrap().insert(
+                        signature,
+                        Pending {
+                            endpoint: Arc::clone(&endpoint),
+                            last_valid_block_height,
+                            since: Instant::now(),
+                        },
+                    );
+                    return Ok(BroadcastReceipt {
+                        signature,
+                        first_endpoint: endpoint,
+                    });
+                }
+                Err(e) => errors.push(format!("[{}] {}", endpoint.url, e)),
+            }
+        }
+
+        Err(anyhow!("所有发送端点均发送失败: {}", errors.join("; ")))
+    }
+
+    /// 重发已广播过的交易
+    ///
+    /// 不计入端点统计，也不改变等待确认结果的交易及其最先返回签名的端点，
+    /// 否则每次重发都会被当作新的交易重复统计
+    pub async fn resend(
+        &self,
+        tx: &VersionedTransaction,
+        send_config: RpcSendTransactionConfig,
+    ) -> Result<()> {
+        let targets = self.targets();
+        let results = join_all(targets.iter().map(|endpoint| {
+            endpoint
+                .client
+                .send_transaction_with_config(tx, send_config)
+        }))
+        .await;
+
+        let mut errors = Vec::new();
+        for (endpoint, result) in targets.iter().zip(results) {
+            match result {
+                Ok(_) => return Ok(()),
+                Err(e) => errors.push(format!("[{}] {}", endpoint.url, e)),
+            }
+        }
+        Err(anyhow!("所有发送端点均重发失败: {}", errors.join("; ")))
+    }
+
+    /// 可用的发送端点，所有端点都被暂停时仍然全部发送
+    fn targets(&self) -> Vec<Arc<Endpoint>> {
+        let targets: Vec<Arc<Endpoint>> = self
+            .endpoints
+            .iter()
+            .filter(|e| e.is_available())
+            .cloned()
+            .collect();
+        if targets.is_empty() {
+            warn!("所有发送端点均已暂停，本次发送到全部端点");
+            return self.endpoints.clone();
+        }
+        targets
+    }
+
+    /// 根据交易确认结果，记录最先发送成功的端点是否上链，并清理已过期的交易
+    ///
+    /// block_height 为当前区块高度，未能获取时按时间判断是否过期
+    pub fn on_confirmation(&self, event: &ConfirmationEvent, block_height: Option<u64>) {
+        let pending = {
+            let mut pending = self.pending.lock().unwrap();
+            let removed = pending.remove(&event.signature);
+            pending.retain(|_, p| !p.is_expired(block_height));
+            removed
+        };
+        let Some(pending) = pending else {
+            return;
+        };
+        if matches!(event.outcome, Outcome::Landed { .. }) {
+            pending
+                .endpoint
+                .stats
+                .landed
+                .fetch_add(1, Ordering::Relaxed);
+        }
+        for s in self.snapshot() {
+            debug!("发送端点状态: {:?}", s);
+        }
+    }
+
+    pub fn snapshot(&self) -> Vec<EndpointSnapshot> {
+        self.endpoints
+            .iter()
+            .map(|e| EndpointSnapshot {
+                url: e.url.clone(),
+                available: e.disabled_until.lock().unwrap().is_none(),
+                sent: e.stats.sent.load(Ordering::Relaxed),
+                failed: e.stats.failed.load(Ordering::Relaxed),
+                first: e.stats.first.load(Ordering::Relaxed),
+                landed: e.stats.landed.load(Ordering::Relaxed),
+                avg_latency_ms: e.stats.avg_latency_ms(),
+            })
+            .collect()
+    }
+}
+
+/// 样本数足够后，延时或失败率超出阈值的端点暂停一段时间
+fn prune(endpoint: &Endpoint, config: &BroadcastConfig) {
+    let sent = endpoint.stats.sent.load(Ordering::Relaxed);
+    if sent < config.min_samples {
+        return;
+    }
+
+    let avg_latency_ms = endpoint.stats.avg_latency_ms().unwrap_or_default();
+    let error_rate = endpoint.stats.error_rate();
+    if should_prune(avg_latency_ms, error_rate, config) {
+        let mut disabled_until = endpoint.disabled_until.lock().unwrap();
+        if disabled_until.is_none() {
+            warn!(
+                "发送端点 {} 平均延时 {} ms，失败率 {:.2}，暂停 {:?}",
+                endpoint.url,
+                avg_latency_ms,
+                error_rate,
+                config.prune_cooldown_to_duration()
+            );
+            *disabled_until = Some(Instant::now() + config.prune_cooldown_to_duration());
+        }
+    }
+}
+
+fn should_prune(avg_latency_ms: u64, error_rate: f64, config: &BroadcastConfig) -> bool {
+    (config.max_latency_ms > 0 && avg_latency_ms > config.max_latency_ms)
+        || error_rate > config.max_error_rate
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_should_prune() {
+        let config = BroadcastConfig {
+            endpoints: vec![],
+            max_latency_ms: 500,
+            max_error_rate: 0.5,
+            min_samples: 10,
+            prune_cooldown_ms: 1000,
+        };
+
+        assert!(!should_prune(100, 0.1, &config));
+        assert!(should_prune(600, 0.1, &config));
+        assert!(should_prune(100, 0.6, &config));
+
+        let config = BroadcastConfig {
+            max_latency_ms: 0,
+            ..config
+        };
+        assert!(!should_prune(10_000, 0.1, &config));
+    }
+
+    #[tokio::test]
+    async fn test_pending_expiry() {
+        let broadcaster = Broadcaster::new(&BroadcastConfig {
+            endpoints: vec!["http://127.0.0.1:8899".to_string()],
+            max_latency_ms: 500,
+            max_error_rate: 0.5,
+            min_samples: 10,
+            prune_cooldown_ms: 1000,
+        })
+        .unwrap();
+        let endpoint = Arc::clone(&broadcaster.endpoints()[0]);
+        let pending = |last_valid_block_height| Pending {
+            endpoint: Arc::clone(&endpoint),
+            last_valid_block_height,
+            since: Instant::now(),
+        };
+
+        let (landed, expired, nonce) = (
+            Signature::new_unique(),
+            Signature::new_unique(),
+            Signature::new_unique(),
+        );
+        {
+            let mut map = broadcaster.pending.lock().unwrap();
+            map.insert(landed, pending(Some(100)));
+            map.insert(expired, pending(Some(150)));
+            map.insert(nonce, pending(None));
+        }
+
+        // 区块高度已超过 landed 的有效期，但其上链结果仍然计入统计
+        broadcaster.on_confirmation(
+            &ConfirmationEvent {
+                signature: landed,
+                label: "rpc".to_string(),
+                outcome: Outcome::Landed { slot: 1 },
+                elapsed: Duration::ZERO,
+            },
+            Some(151),
+        );
+        assert_eq!(endpoint.stats.landed.load(Ordering::Relaxed), 1);
+
+        // 过期的交易被移除，不知道有效期的交易按时间判断
+        let map = broadcaster.pending.lock().unwrap();
+        assert!(!map.contains_key(&expired));
+        assert!(map.contains_key(&nonce));
+    }
+
+    #[tokio::test]
+    async fn test_resend_keeps_stats() {
+        let broadcaster = Broadcaster::new(&BroadcastConfig {
+            // 无法连接的端点
+            endpoints: vec!["http://127.0.0.1:1".to_string()],
+            max_latency_ms: 0,
+            max_error_rate: 1.0,
+            min_samples: 10,
+            prune_cooldown_ms: 1000,
+        })
+        .unwrap();
+        let endpoint = Arc::clone(&broadcaster.endpoints()[0]);
+        let signature = Signature::new_unique();
+        broadcaster.pending.lock().unwrap().insert(
+            signature,
+            Pending {
+                endpoint: Arc::clone(&endpoint),
+                last_valid_block_height: Some(100),
+                since: Instant::now(),
+            },
+        );
+
+        let send_config = RpcSendTransactionConfig {
+            skip_preflight: true,
+            ..Default::default()
+        };
+        assert!(
+            broadcaster
+                .resend(&VersionedTransaction::default(), send_config)
+                .await
+                .is_err()
+        );
+        assert_eq!(endpoint.stats.sent.load(Ordering::Relaxed), 0);
+        assert_eq!(endpoint.stats.failed.load(Ordering::Relaxed), 0);
+        assert_eq!(
+            broadcaster.pending.lock().unwrap()[&signature].last_valid_block_height,
+            Some(100)
+        );
+    }
+}
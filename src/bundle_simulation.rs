@@ -0,0 +1,295 @@
+use anyhow::{Result, anyhow};
+use base64::Engine as _;
+use serde::Deserialize;
+use serde_json::{Value, json};
+use solana_sdk::{pubkey::Pubkey, transaction::VersionedTransaction};
+use std::time::Duration;
+use tracing::debug;
+
+/// simulateBundle 响应中的账户信息
+#[derive(Debug, Clone, Deserialize)]
+pub struct UiAccount {
+    pub lamports: u64,
+    /// [data, encoding]
+    pub data: (String, String),
+}
+
+impl UiAccount {
+    /// SPL Token 账户余额，位于账户数据第 64..72 字节
+    fn token_amount(&self) -> Option<u64> {
+        let data = base64::engine::general_purpose::STANDARD
+            .decode(&self.data.0)
+            .ok()?;
+        let amount = data.get(64..72)?;
+        Some(u64::from_le_bytes(amount.try_into().ok()?))
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TransactionResult {
+    #[serde(default)]
+    pub err: Option<Value>,
+    #[serde(default)]
+    pub logs: Option<Vec<String>>,
+    #[serde(default)]
+    pub units_consumed: Option<u64>,
+    #[serde(default)]
+    pub pre_execution_accounts: Option<Vec<Option<UiAccount>>>,
+    #[serde(default)]
+    pub post_execution_accounts: Option<Vec<Option<UiAccount>>>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SimulateBundleValue {
+    /// "succeeded" 或 {"failed": {"error": ..., "tx_signature": ...}}
+    pub summary: Value,
+    #[serde(default)]
+    pub transaction_results: Vec<TransactionResult>,
+}
+
+/// 需要监控余额的账户
+#[derive(Debug, Clone, Copy)]
+pub enum WatchedAccount {
+    /// 原生 SOL 账户，读取 lamports
+    Native(Pubkey),
+    /// Token 账户，读取 amount
+    Token(Pubkey),
+}
+
+impl WatchedAccount {
+    fn pubkey(&self) -> Pubkey {
+        match self {
+            WatchedAccount::Native(pubkey) | WatchedAccount::Token(pubkey) => *pubkey,
+        }
+    }
+
+    fn balance(&self, account: &UiAccount) -> Option<u64> {
+        match self {
+            WatchedAccount::Native(_) => Some(account.lamports),
+            WatchedAccount::Token(_) => account.token_amount(),
+        }
+    }
+}
+
+/// bundle 模拟结果
+#[derive(Debug, Clone)]
+pub struct BundleSimulation {
+    pub value: SimulateBundleValue,
+    pub watched: WatchedAccount,
+}
+
+impl BundleSimulation {
+    pub fn succeeded(&self) -> bool {
+        self.value.summary.as_str() == Some("succeeded")
+    }
+
+    /// 失败原因
+    pub fn error(&self) -> Option<&Value> {
+        self.value.summary.get("failed")
+    }
+
+    /// 每笔交易的日志
+    pub fn logs(&self) -> Vec<&[String]> {
+        self.value
+            .transaction_results
+            .iter()
+            .map(|r| r.logs.as_deref().unwrap_or_default())
+            .collect()
+    }
+
+    /// 每笔交易执行前后监控账户的余额
+    pub fn balances(&self) -> Vec<(Option<u64>, Option<u64>)> {
+        self.value
+            .transaction_results
+            .iter()
+            .map(|r| {
+                let balance = |accounts: &Option<Vec<Option<UiAccount>>>| {
+                    accounts
+                        .as_ref()
+                        .and_then(|a| a.first())
+                        .and_then(|a| a.as_ref())
+                        .and_then(|a| self.watched.balance(a))
+                };
+                (
+                    balance(&r.pre_execution_accounts),
+                    balance(&r.post_execution_accounts),
+                )
+            })
+            .collect()
+    }
+
+    /// 模拟利润：第一笔交易执行前与最后一笔交易执行后的余额差
+    pub fn profit(&self) -> Option<i64> {
+        let balances = self.balances();
+        let before = balances.first()?.0?;
+        let after = balances.last()?.1?;
+        Some(after as i64 - before as i64)
+    }
+}
+
+/// 通过支持 Jito 的 RPC 节点调用 simulateBundle
+pub struct BundleSimulator {
+    client: reqwest::Client,
+    endpoint: String,
+    timeout: Duration,
+}
+
+impl BundleSimulator {
+    pub fn new(client: reqwest::Client, endpoint: String, timeout: Duration) -> Self {
+        Self {
+            client,
+            endpoint,
+            timeout,
+        }
+    }
+
+    pub async fn simulate(
+        &self,
+        txs: &[VersionedTransaction],
+        watched: WatchedAccount,
+    ) -> Result<BundleSimulation> {
+        let encoded_txs = txs
+            .iter()
+            .map(|tx| Ok(base64::engine::general_purpose::STANDARD.encode(bincode::serialize(tx)?)))
+            .collect::<Result<Vec<String>>>()?;
+
+        // 每笔交易都返回监控账户执行前后的状态
+        let accounts_config = json!({
+            "addresses": [watched.pubkey().to_string()],
+            "encoding": "base64",
+        });
+        let accounts_configs = vec![accounts_config; txs.len()];
+
+        let request = json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "simulateBundle",
+            "params": [
+                {"encodedTransactions": encoded_txs},
+                {
+                    "preExecutionAccountsConfigs": accounts_configs,
+                    "postExecutionAccountsConfigs": accounts_configs,
+                    "skipSigVerify": true,
+                    "replaceRecentBlockhash": false,
+                }
+            ],
+        });
+
+        let res = self
+            .client
+            .post(&self.endpoint)
+            .json(&request)
+            .timeout(self.timeout)
+            .send()
+            .await?
+            .error_for_status()?
+            .json::<Value>()
+            .await?;
+        debug!("simulateBundle response = {}", res);
+
+        parse_simulate_bundle_response(res, watched)
+    }
+}
+
+fn parse_simulate_bundle_response(res: Value, watched: WatchedAccount) -> Result<BundleSimulation> {
+    if let Some(error) = res.get("error") {
+        return Err(anyhow!("simulateBundle 调用失败: {}", error));
+    }
+
+    let value = res
+        .get("result")
+        .and_then(|r| r.get("value"))
+        .cloned()
+        .ok_or_else(|| anyhow!("simulateBundle 响应格式错误: {}", res))?;
+
+    Ok(BundleSimulation {
+        value: serde_json::from_value(value)?,
+        watched,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn token_account_data(amount: u64) -> String {
+        let mut data = vec![0u8; 165];
+        data[64..72].copy_from_slice(&amount.to_le_bytes());
+        base64::engine::general_purpose::STANDARD.encode(data)
+    }
+
+    #[test]
+    fn test_parse_succeeded_bundle() {
+        let res = json!({
+            "jsonrpc": "2.0",
+            "result": {
+                "context": {"apiVersion": "2.2.0", "slot": 1},
+                "value": {
+                    "summary": "succeeded",
+                    "transactionResults": [
+                        {
+                            "err": null,
+                            "logs": ["Program log: leg 1"],
+                            "unitsConsumed": 1000,
+                            "preExecutionAccounts": [{"lamports": 2039280, "data": [token_account_data(1_000), "base64"], "owner": "TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA", "executable": false, "rentEpoch": 0}],
+                            "postExecutionAccounts": [{"lamports": 2039280, "data": [token_account_data(0), "base64"], "owner": "TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA", "executable": false, "rentEpoch": 0}],
+                            "returnData": null
+                        },
+                        {
+                            "err": null,
+                            "logs": ["Program log: leg 2"],
+                            "unitsConsumed": 2000,
+                            "preExecutionAccounts": [{"lamports": 2039280, "data": [token_account_data(0), "base64"], "owner": "TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA", "executable": false, "rentEpoch": 0}],
+                            "postExecutionAccounts": [{"lamports": 2039280, "data": [token_account_data(1_250), "base64"], "owner": "TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA", "executable": false, "rentEpoch": 0}],
+                            "returnData": null
+                        }
+                    ]
+                }
+            },
+            "id": 1
+        });
+
+        let sim = parse_simulate_bundle_response(res, WatchedAccount::Token(Pubkey::new_unique()))
+            .unwrap();
+        assert!(sim.succeeded());
+        assert!(sim.error().is_none());
+        assert_eq!(sim.logs()[1], ["Program log: leg 2".to_string()]);
+        assert_eq!(
+            sim.balances(),
+            vec![(Some(1_000), Some(0)), (Some(0), Some(1_250))]
+        );
+        assert_eq!(sim.profit(), Some(250));
+    }
+
+    #[test]
+    fn test_parse_failed_bundle() {
+        let res = json!({
+            "jsonrpc": "2.0",
+            "result": {
+                "context": {"slot": 1},
+                "value": {
+                    "summary": {"failed": {"error": {"TransactionFailure": [[0], "custom program error: 0x64"]}, "tx_signature": "abc"}},
+                    "transactionResults": []
+                }
+            },
+            "id": 1
+        });
+
+        let sim = parse_simulate_bundle_response(res, WatchedAccount::Native(Pubkey::new_unique()))
+            .unwrap();
+        assert!(!sim.succeeded());
+        assert!(sim.error().is_some());
+        assert_eq!(sim.profit(), None);
+    }
+
+    #[test]
+    fn test_parse_rpc_error() {
+        let res = json!({"jsonrpc": "2.0", "error": {"code": -32601, "message": "Method not found"}, "id": 1});
+        assert!(
+            parse_simulate_bundle_response(res, WatchedAccount::Native(Pubkey::new_unique()))
+                .is_err()
+        );
+    }
+}
@@ -4,6 +4,7 @@ use serde::{Deserialize, Deserializer, Serialize};
 use serde_json::Value;
 use serde_repr::{Deserialize_repr, Serialize_repr};
 use solana_sdk::signer::keypair::Keypair;
+use std::collections::HashMap;
 use std::fmt::Display;
 use std::time::Duration;
 
@@ -64,6 +65,41 @@ pub struct Config {
 
     #[serde(default)]
     pub jito: JitoConfig,
+
+    #[serde(default)]
+    pub broadcast: BroadcastConfig,
+
+    #[serde(default)]
+    pub tpu: TpuConfig,
+
+    #[serde(default)]
+    pub hybrid: HybridConfig,
+
+    #[serde(default)]
+    pub leader: LeaderConfig,
+
+    #[serde(default)]
+    pub confirmation: ConfirmationConfig,
+
+    #[serde(default)]
+    pub rebroadcast: RebroadcastConfig,
+
+    #[serde(default)]
+    pub nonce: NonceConfig,
+
+    #[serde(default)]
+    pub blockhash: BlockhashConfig,
+
+    #[serde(default)]
+    pub quote_guard: QuoteGuardConfig,
+
+    /// 交易提交通道："rpc"、"jito"、"tpu" 或 relays 中配置的中继名称，
+    /// 为空时根据 hybrid.enabled、jito.bundle_submit 选择
+    #[serde(default)]
+    pub senders: Vec<String>,
+
+    #[serde(default)]
+    pub relays: Vec<RelayConfig>,
 }
 
 fn default_profit_protect_program_id() -> String {
@@ -86,6 +122,317 @@ impl Config {
     pub fn keypair(&self) -> Keypair {
         util::load_keypair(&self.private_key).unwrap()
     }
+
+    /// 启用的交易提交通道
+    pub fn sender_names(&self) -> Vec<String> {
+        if !self.senders.is_empty() {
+            return self.senders.clone();
+        }
+        if self.hybrid.enabled {
+            return vec![SENDER_JITO.to_string(), SENDER_RPC.to_string()];
+        }
+        if self.jito.bundle_submit {
+            vec![SENDER_JITO.to_string()]
+        } else {
+            vec![SENDER_RPC.to_string()]
+        }
+    }
+
+    /// 是否通过 Jito 提交
+    pub fn jito_enabled(&self) -> bool {
+        self.sender_names().iter().any(|name| name == SENDER_JITO)
+    }
+
+    /// 是否有提交通道需要优先费
+    pub fn priority_fee_required(&self) -> bool {
+        self.sender_names().iter().any(|name| {
+            name == SENDER_RPC
+                || self
+                    .relays
+                    .iter()
+                    .any(|relay| &relay.name == name && relay.priority_fee)
+        })
+    }
+}
+
+pub const SENDER_RPC: &str = "rpc";
+pub const SENDER_JITO: &str = "jito";
+pub const SENDER_TPU: &str = "tpu";
+
+/// 混合提交配置，同时通过 Jito bundle 及 RPC 提交同一个套利
+#[derive(Deserialize, Default, Clone, Debug, Serialize)]
+pub struct HybridConfig {
+    #[serde(default)]
+    pub enabled: bool,
+
+    /// RPC 版本交易使用的 compute unit price(micro-lamports)，0 表示使用 Jupiter 返回的优先费
+    #[serde(default)]
+    pub compute_unit_price: u64,
+}
+
+/// 交易确认跟踪配置
+#[derive(Deserialize, Clone, Debug, Serialize)]
+pub struct ConfirmationConfig {
+    /// 批量查询交易状态的间隔(ms)
+    #[serde(default = "default_confirmation_poll_interval_ms")]
+    pub poll_interval_ms: u64,
+
+    /// 提交后超过该时间(ms)仍未上链视为过期
+    #[serde(default = "default_confirmation_timeout_ms")]
+    pub timeout_ms: u64,
+}
+
+impl Default for ConfirmationConfig {
+    fn default() -> Self {
+        Self {
+            poll_interval_ms: default_confirmation_poll_interval_ms(),
+            timeout_ms: default_confirmation_timeout_ms(),
+        }
+    }
+}
+
+impl ConfirmationConfig {
+    pub fn poll_interval_to_duration(&self) -> Duration {
+        Duration::from_millis(self.poll_interval_ms)
+    }
+
+    pub fn timeout_to_duration(&self) -> Duration {
+        Duration::from_millis(self.timeout_ms)
+    }
+}
+
+fn default_confirmation_poll_interval_ms() -> u64 {
+    500
+}
+
+fn default_confirmation_timeout_ms() -> u64 {
+    60_000
+}
+
+/// 跳过预检查时定期重发交易，直到上链或 blockhash 过期
+#[derive(Deserialize, Default, Clone, Debug, Serialize)]
+pub struct RebroadcastConfig {
+    #[serde(default)]
+    pub enabled: bool,
+
+    /// 重发间隔(ms)
+    #[serde(default = "default_rebroadcast_interval_ms")]
+    pub interval_ms: u64,
+}
+
+impl RebroadcastConfig {
+    pub fn interval_to_duration(&self) -> Duration {
+        Duration::from_millis(self.interval_ms)
+    }
+}
+
+fn default_rebroadcast_interval_ms() -> u64 {
+    2_000
+}
+
+/// 使用持久 nonce 代替 recent blockhash 签名套利交易
+#[derive(Deserialize, Default, Clone, Debug, Serialize)]
+pub struct NonceConfig {
+    #[serde(default)]
+    pub enabled: bool,
+
+    /// nonce 账户地址，authority 需为当前钱包，可通过 `arbitrage-bot nonce create` 创建
+    #[serde(default)]
+    pub account: String,
+
+    /// nonce 值的刷新间隔(ms)
+    #[serde(default = "default_nonce_refresh_interval_ms")]
+    pub refresh_interval_ms: u64,
+}
+
+impl NonceConfig {
+    pub fn refresh_interval_to_duration(&self) -> Duration {
+        Duration::from_millis(self.refresh_interval_ms)
+    }
+}
+
+fn default_nonce_refresh_interval_ms() -> u64 {
+    1_000
+}
+
+/// blockhash 及 slot 跟踪配置
+#[derive(Deserialize, Clone, Debug, Serialize)]
+pub struct BlockhashConfig {
+    /// 订阅 slot 使用的 WebSocket 地址，为空时根据 rpc_endpoint 推导
+    #[serde(default)]
+    pub websocket_url: Option<String>,
+
+    /// WebSocket 不可用时轮询 blockhash 的间隔(ms)
+    #[serde(default = "default_blockhash_poll_interval_ms")]
+    pub poll_interval_ms: u64,
+
+    /// blockhash 超过该时间(ms)未更新时拒绝签名，0 表示不检查
+    #[serde(default = "default_blockhash_max_age_ms")]
+    pub max_age_ms: u64,
+}
+
+impl Default for BlockhashConfig {
+    fn default() -> Self {
+        Self {
+            websocket_url: None,
+            poll_interval_ms: default_blockhash_poll_interval_ms(),
+            max_age_ms: default_blockhash_max_age_ms(),
+        }
+    }
+}
+
+impl BlockhashConfig {
+    pub fn poll_interval_to_duration(&self) -> Duration {
+        Duration::from_millis(self.poll_interval_ms)
+    }
+
+    pub fn max_age_to_duration(&self) -> Duration {
+        Duration::from_millis(self.max_age_ms)
+    }
+}
+
+fn default_blockhash_poll_interval_ms() -> u64 {
+    2_000
+}
+
+fn default_blockhash_max_age_ms() -> u64 {
+    10_000
+}
+
+/// 根据报价的 context_slot 拒绝过时的套利机会
+#[derive(Deserialize, Clone, Debug, Serialize)]
+pub struct QuoteGuardConfig {
+    /// 报价落后当前 slot 超过该值时拒绝，0 表示不检查
+    #[serde(default = "default_quote_guard_max_slot_lag")]
+    pub max_slot_lag: u64,
+
+    /// 两次报价的 context_slot 相差超过该值时拒绝，0 表示不检查
+    #[serde(default = "default_quote_guard_max_leg_slot_span")]
+    pub max_leg_slot_span: u64,
+}
+
+impl Default for QuoteGuardConfig {
+    fn default() -> Self {
+        Self {
+            max_slot_lag: default_quote_guard_max_slot_lag(),
+            max_leg_slot_span: default_quote_guard_max_leg_slot_span(),
+        }
+    }
+}
+
+fn default_quote_guard_max_slot_lag() -> u64 {
+    20
+}
+
+fn default_quote_guard_max_leg_slot_span() -> u64 {
+    10
+}
+
+/// 根据 Leader 是否运行 Jito 客户端调度提交
+#[derive(Deserialize, Default, Clone, Debug, Serialize)]
+pub struct LeaderConfig {
+    #[serde(default)]
+    pub enabled: bool,
+
+    /// Jito Leader 在该 slot 数以内出块时通过 bundle 提交
+    #[serde(default = "default_leader_lookahead_slots")]
+    pub lookahead_slots: u64,
+
+    /// 非紧急的套利最多等待多少个 slot，等待 Jito Leader 出块
+    #[serde(default = "default_leader_max_delay_slots")]
+    pub max_delay_slots: u64,
+
+    /// 利润不低于该值的套利视为紧急，不等待 Jito Leader，直接通过其它通道提交，0 表示不启用
+    #[serde(default)]
+    pub urgent_profit_amount: u64,
+
+    /// leader schedule 及 Jito 验证者列表的刷新间隔(ms)
+    #[serde(default = "default_leader_refresh_interval_ms")]
+    pub refresh_interval_ms: u64,
+
+    /// Jito 验证者列表接口
+    #[serde(default = "default_leader_validators_api")]
+    pub validators_api: String,
+}
+
+impl LeaderConfig {
+    pub fn refresh_interval_to_duration(&self) -> Duration {
+        Duration::from_millis(self.refresh_interval_ms)
+    }
+}
+
+fn default_leader_lookahead_slots() -> u64 {
+    2
+}
+fn default_leader_max_delay_slots() -> u64 {
+    8
+}
+fn default_leader_refresh_interval_ms() -> u64 {
+    600_000
+}
+fn default_leader_validators_api() -> String {
+    "https://kobe.mainnet.jito.network/api/v1/validators".to_string()
+}
+
+/// TPU 直连配置
+#[derive(Deserialize, Clone, Debug, Serialize)]
+pub struct TpuConfig {
+    /// 发送到当前及后续多少个 slot 的 Leader
+    #[serde(default = "default_tpu_fanout_slots")]
+    pub fanout_slots: u64,
+
+    /// 订阅 slot 使用的 WebSocket 地址，为空时根据 rpc_endpoint 推导
+    #[serde(default)]
+    pub websocket_url: Option<String>,
+}
+
+impl Default for TpuConfig {
+    fn default() -> Self {
+        Self {
+            fanout_slots: default_tpu_fanout_slots(),
+            websocket_url: None,
+        }
+    }
+}
+
+fn default_tpu_fanout_slots() -> u64 {
+    12
+}
+
+/// 第三方中继配置，通过 HTTP JSON-RPC 提交交易
+#[derive(Deserialize, Clone, Debug, Serialize)]
+pub struct RelayConfig {
+    pub name: String,
+
+    pub endpoint: String,
+
+    /// 单笔交易提交方法
+    #[serde(default = "default_relay_method")]
+    pub method: String,
+
+    /// bundle 提交方法，未配置时该中继不支持多笔交易
+    #[serde(default)]
+    pub bundle_method: Option<String>,
+
+    /// 附加的请求头，如认证信息
+    #[serde(default)]
+    pub headers: HashMap<String, String>,
+
+    /// 中继指定的小费账户，每次随机选择一个
+    #[serde(default)]
+    pub tip_accounts: Vec<String>,
+
+    /// 小费金额，0 表示不支付小费
+    #[serde(default)]
+    pub tip_amount: u64,
+
+    /// 是否保留优先费指令
+    #[serde(default)]
+    pub priority_fee: bool,
+}
+
+fn default_relay_method() -> String {
+    "sendTransaction".to_string()
 }
 
 #[derive(Deserialize, Default, Clone, Debug, Serialize)]
@@ -152,6 +499,49 @@ pub struct JitoConfig {
     #[serde(default = "default_jito_rpc_endpoint")]
     pub rpc_endpoint: String,
 
+    /// 多区域 Block Engine 列表，为空时只使用 rpc_endpoint
+    #[serde(default)]
+    pub rpc_endpoints: Vec<String>,
+
+    /// 每个 bundle 同时发送的区域数量，0 表示发送到全部活跃区域
+    #[serde(default)]
+    pub fan_out: usize,
+
+    #[serde(default)]
+    pub region_mode: RegionMode,
+
+    /// 区域延时探测间隔(ms)
+    #[serde(default = "default_region_probe_interval_ms")]
+    pub region_probe_interval_ms: u64,
+
+    /// 批量合并时间窗口(ms)，窗口内的多个套利机会合并到一个 bundle，0 表示不合并
+    #[serde(default)]
+    pub batch_window_ms: u64,
+
+    /// 支持 simulateBundle 的 RPC 地址，配置后提交前先模拟 bundle
+    #[serde(default)]
+    pub simulation_endpoint: Option<String>,
+
+    /// Jito 认证 UUID，未配置时使用匿名访问的限流
+    #[serde(default)]
+    pub uuid: Option<String>,
+
+    /// 每秒允许发送的 bundle 数量，0 表示不限流
+    #[serde(default)]
+    pub rate_limit: f64,
+
+    /// 令牌桶容量，允许的突发 bundle 数量
+    #[serde(default = "default_rate_limit_burst")]
+    pub rate_limit_burst: u32,
+
+    /// 令牌不足时最多排队的 bundle 数量，超出时丢弃利润最低的 bundle
+    #[serde(default = "default_max_pending_bundles")]
+    pub max_pending_bundles: usize,
+
+    /// bundle 排队等待的最长时间(ms)，超时后放弃
+    #[serde(default = "default_max_pending_ms")]
+    pub max_pending_ms: u64,
+
     #[serde(default = "default_fixed_tip_amount")]
     pub fixed_tip_amount: u64,
 
@@ -171,6 +561,105 @@ pub struct JitoConfig {
     pub bundle_statuses_checking: bool,
 }
 
+impl JitoConfig {
+    /// 所有 Block Engine 地址，未配置 rpc_endpoints 时兼容旧的单个 rpc_endpoint
+    pub fn endpoints(&self) -> Vec<String> {
+        if self.rpc_endpoints.is_empty() {
+            vec![self.rpc_endpoint.clone()]
+        } else {
+            self.rpc_endpoints.clone()
+        }
+    }
+
+    pub fn region_probe_interval_to_duration(&self) -> Duration {
+        Duration::from_millis(self.region_probe_interval_ms)
+    }
+
+    pub fn batch_window_to_duration(&self) -> Duration {
+        Duration::from_millis(self.batch_window_ms)
+    }
+
+    pub fn max_pending_to_duration(&self) -> Duration {
+        Duration::from_millis(self.max_pending_ms)
+    }
+}
+
+/// 多端点广播交易配置，使用 rpc 通道提交时生效
+#[derive(Deserialize, Default, Clone, Debug, Serialize)]
+pub struct BroadcastConfig {
+    /// 交易发送端点，如质押 RPC、SWQoS 服务商，为空时只发送到 rpc_endpoint
+    #[serde(default)]
+    pub endpoints: Vec<String>,
+
+    /// 端点平均发送延时上限(ms)，超出后暂停该端点，0 表示不检查
+    #[serde(default = "default_broadcast_max_latency_ms")]
+    pub max_latency_ms: u64,
+
+    /// 端点发送失败率上限，超出后暂停该端点
+    #[serde(default = "default_broadcast_max_error_rate")]
+    pub max_error_rate: f64,
+
+    /// 统计样本数达到该值后才判断是否暂停端点
+    #[serde(default = "default_broadcast_min_samples")]
+    pub min_samples: u64,
+
+    /// 端点暂停时长(ms)，到期后清空统计重新启用
+    #[serde(default = "default_broadcast_prune_cooldown_ms")]
+    pub prune_cooldown_ms: u64,
+}
+
+impl BroadcastConfig {
+    pub fn prune_cooldown_to_duration(&self) -> Duration {
+        Duration::from_millis(self.prune_cooldown_ms)
+    }
+}
+
+fn default_broadcast_max_latency_ms() -> u64 {
+    1_000
+}
+fn default_broadcast_max_error_rate() -> f64 {
+    0.5
+}
+fn default_broadcast_min_samples() -> u64 {
+    20
+}
+fn default_broadcast_prune_cooldown_ms() -> u64 {
+    60_000
+}
+
+/// Jito 区域选择方式
+#[derive(Deserialize, Default, Clone, Copy, Debug, Serialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum RegionMode {
+    /// 所有配置的区域均为活跃状态
+    #[default]
+    All,
+    /// 定期探测延时，只保留延时最低的 fan_out 个区域
+    Auto,
+}
+
+impl Display for RegionMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RegionMode::All => write!(f, "全部区域"),
+            RegionMode::Auto => write!(f, "自动选择(最低延时)"),
+        }
+    }
+}
+
+fn default_region_probe_interval_ms() -> u64 {
+    30_000
+}
+fn default_rate_limit_burst() -> u32 {
+    1
+}
+fn default_max_pending_bundles() -> usize {
+    5
+}
+fn default_max_pending_ms() -> u64 {
+    400
+}
+
 fn default_min_profit_amount() -> u64 {
     8_000_000
 }
@@ -259,9 +748,97 @@ impl Display for FlashLoanState {
 
 #[derive(Deserialize, Default, Clone, Debug, Serialize)]
 pub struct FlashLoan {
+    /// 直接写在 [flash_loan] 下的来源，兼容只配置一个来源的写法
+    #[serde(flatten)]
+    pub source: FlashLoanSource,
+
+    /// 其它闪电贷来源，每次套利选择流动性充足且费用最低的来源
+    #[serde(default)]
+    pub sources: Vec<FlashLoanSource>,
+
+    #[serde(default = "default_marginfi_group")]
+    pub marginfi_group: String,
+
+    /// 后台刷新 reserve 状态的间隔
+    #[serde(default = "default_flash_loan_refresh_interval_ms")]
+    pub refresh_interval_ms: u64,
+
+    #[serde(default)]
+    pub leverage: LeverageConfig,
+
+    /// Kamino 推荐人账户，为空时不使用推荐人，推荐人需在每个 reserve 上初始化 token state
+    #[serde(default)]
+    pub kamino_referrer: String,
+}
+
+fn default_flash_loan_refresh_interval_ms() -> u64 {
+    2000
+}
+
+impl FlashLoan {
+    pub fn refresh_interval_to_duration(&self) -> Duration {
+        Duration::from_millis(self.refresh_interval_ms)
+    }
+
+    /// 全部已配置的来源，reserve 为空的来源忽略
+    pub fn all_sources(&self) -> impl Iterator<Item = &FlashLoanSource> {
+        std::iter::once(&self.source)
+            .chain(self.sources.iter())
+            .filter(|source| !source.reserve.is_empty())
+    }
+}
+
+#[derive(Deserialize, Default, Clone, Debug, Serialize)]
+pub struct FlashLoanSource {
+    /// 闪电贷平台: kamino / marginfi
+    #[serde(default = "default_flash_loan_provider")]
+    pub provider: String,
+
+    /// kamino 为 reserve 地址，marginfi 为 bank 地址
     #[serde(default)]
     pub reserve: String,
 
+    /// marginfi 账户，可通过 `flashloan marginfi-init` 创建
     #[serde(default)]
-    pub borrow_rate: f64,
+    pub marginfi_account: String,
+}
+
+/// 闪电贷杠杆，根据可借流动性及报价的价格影响调整交易数量
+#[derive(Deserialize, Clone, Debug, Serialize)]
+pub struct LeverageConfig {
+    #[serde(default)]
+    pub enabled: bool,
+
+    /// 交易数量上限，0 表示只受钱包余额与可借流动性限制
+    #[serde(default)]
+    pub max_amount: u64,
+
+    /// 两次报价的价格影响(priceImpactPct)之和的上限
+    #[serde(default = "default_leverage_max_price_impact")]
+    pub max_price_impact: f64,
+}
+
+impl Default for LeverageConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            max_amount: 0,
+            max_price_impact: default_leverage_max_price_impact(),
+        }
+    }
+}
+
+fn default_leverage_max_price_impact() -> f64 {
+    0.005
+}
+
+pub const FLASH_LOAN_KAMINO: &str = "kamino";
+pub const FLASH_LOAN_MARGINFI: &str = "marginfi";
+
+fn default_flash_loan_provider() -> String {
+    FLASH_LOAN_KAMINO.to_string()
+}
+
+fn default_marginfi_group() -> String {
+    crate::flashloan::marginfi::MARGINFI_GROUP.to_string()
 }
@@ -0,0 +1,307 @@
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::{signature::Signature, transaction::TransactionError};
+use solana_transaction_status::TransactionStatus;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use tokio::sync::broadcast;
+use tokio::time::{Duration, Instant};
+use tracing::debug;
+
+/// getSignatureStatuses 单次最多查询的签名数量
+const MAX_SIGNATURES_PER_REQUEST: usize = 256;
+
+/// 交易的最终结果
+#[derive(Debug, Clone, PartialEq)]
+pub enum Outcome {
+    /// 交易上链且执行成功
+    Landed { slot: u64 },
+    /// 交易上链但执行失败
+    Failed { slot: u64, error: TransactionError },
+    /// 超时或 blockhash 过期仍未上链
+    Expired,
+}
+
+#[derive(Debug, Clone)]
+pub struct ConfirmationEvent {
+    pub signature: Signature,
+    /// 提交通道等来源信息
+    pub label: String,
+    pub outcome: Outcome,
+    /// 从开始跟踪到得出结果的耗时
+    pub elapsed: Duration,
+}
+
+struct Pending {
+    label: String,
+    since: Instant,
+    /// 交易 blockhash 的 last_valid_block_height，设置后以区块高度判断是否过期
+    last_valid_block_height: Option<u64>,
+}
+
+impl Pending {
+    fn is_expired(&self, block_height: Option<u64>, timeout: Duration) -> bool {
+        match (self.last_valid_block_height, block_height) {
+            (Some(last_valid), Some(height)) => height > last_valid,
+            // 未能获取区块高度时仍按超时时间判断
+            _ => self.since.elapsed() > timeout,
+        }
+    }
+}
+
+/// 交易确认跟踪服务
+///
+/// 提交后登记签名即可返回，由后台任务批量调用 getSignatureStatuses 查询状态，
+/// 通过 broadcast 通道发送上链、失败、过期事件
+pub struct ConfirmationTracker {
+    pending: Mutex<HashMap<Signature, Pending>>,
+    events: broadcast::Sender<ConfirmationEvent>,
+    timeout: Duration,
+    /// 最近一次查询到的区块高度，0 表示尚未查询
+    block_height: AtomicU64,
+}
+
+impl ConfirmationTracker {
+    /// 创建跟踪服务，需要调用 start 启动后台轮询
+    pub fn new(timeout: Duration) -> Self {
+        let (events, _) = broadcast::channel(1024);
+        Self {
+            pending: Mutex::new(HashMap::new()),
+            events,
+            timeout,
+            block_height: AtomicU64::new(0),
+        }
+    }
+
+    pub fn start(rpc_client: Arc<RpcClient>, interval: Duration, timeout: Duration) -> Arc<Self> {
+        let tracker = Arc::new(Self::new(timeout));
+
+        let poller = Arc::clone(&tracker);
+        tokio::spawn(async move {
+            loop {
+                tokio::time::sleep(interval).await;
+                poller.poll(&rpc_client).await;
+            }
+        });
+
+        tracker
+    }
+
+    /// 登记需要跟踪的签名
+    pub fn track(&self, signature: Signature, label: impl Into<String>) {
+        self.track_until(signature, label, None);
+    }
+
+    /// 登记需要跟踪的签名，区块高度超过 last_valid_block_height 后视为过期
+    ///
+    /// 重复登记时更新 label，保留开始跟踪的时间
+    pub fn track_until(
+        &self,
+        signature: Signature,
+        label: impl Into<String>,
+        last_valid_block_height: Option<u64>,
+    ) {
+        let label = label.into();
+        self.pending
+            .lock()
+            .unwrap()
+            .entry(signature)
+            .and_modify(|pending| {
+                pending.label = label.clone();
+                if last_valid_block_height.is_some() {
+                    pending.last_valid_block_height = last_valid_block_height;
+                }
+            })
+            .or_insert_with(|| Pending {
+                label,
+                since: Instant::now(),
+                last_valid_block_height,
+            });
+    }
+
+    pub fn is_pending(&self, signature: &Signature) -> bool {
+        self.pending.lock().unwrap().contains_key(signature)
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<ConfirmationEvent> {
+        self.events.subscribe()
+    }
+
+    /// 最近一次轮询时查询到的区块高度
+    pub fn block_height(&self) -> Option<u64> {
+        match self.block_height.load(Ordering::Relaxed) {
+            0 => None,
+            height => Some(height),
+        }
+    }
+
+    pub fn pending_count(&self) -> usize {
+        self.pending.lock().unwrap().len()
+    }
+
+    async fn poll(&self, rpc_client: &RpcClient) {
+        let (signatures, by_height) = {
+            let pending = self.pending.lock().unwrap();
+            let signatures: Vec<Signature> = pending.keys().copied().collect();
+            let by_height = pending
+                .values()
+                .any(|p| p.last_valid_block_height.is_some());
+            (signatures, by_height)
+        };
+        if signatures.is_empty() {
+            return;
+        }
+
+        // 先获取区块高度再查询状态，确保过期前最后一次查询到的状态已包含在内
+        let block_height = if by_height {
+            match rpc_client.get_block_height().await {
+                Ok(height) => {
+                    self.block_height.store(height, Ordering::Relaxed);
+                    Some(height)
+                }
+                Err(e) => {
+                    debug!("get_block_height error: {}", e);
+                    None
+                }
+            }
+        } else {
+            None
+        };
+
+        for chunk in signatures.chunks(MAX_SIGNATURES_PER_REQUEST) {
+            match rpc_client.get_signature_statuses(chunk).await {
+                Ok(res) => {
+                    for (signature, status) in chunk.iter().zip(res.value) {
+                        if let Some(outcome) = status.and_then(outcome) {
+                            self.resolve(signature, outcome);
+                        }
+                    }
+                }
+                Err(e) => debug!("get_signature_statuses error: {}", e),
+            }
+        }
+
+        // 超时或 blockhash 过期仍未上链
+        let expired: Vec<Signature> = self
+            .pending
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(_, p)| p.is_expired(block_height, self.timeout))
+            .map(|(signature, _)| *signature)
+            .collect();
+        for signature in expired {
+            self.resolve(&signature, Outcome::Expired);
+        }
+    }
+
+    pub(crate) fn resolve(&self, signature: &Signature, outcome: Outcome) {
+        let Some(pending) = self.pending.lock().unwrap().remove(signature) else {
+            return;
+        };
+        // 没有订阅者时发送失败，忽略即可
+        _ = self.events.send(ConfirmationEvent {
+            signature: *signature,
+            label: pending.label,
+            outcome,
+            elapsed: pending.since.elapsed(),
+        });
+    }
+}
+
+/// confirmed 及以上确认级别的交易视为已得出结果
+fn outcome(status: TransactionStatus) -> Option<Outcome> {
+    if !status.satisfies_commitment(solana_sdk::commitment_config::CommitmentConfig::confirmed()) {
+        return None;
+    }
+    Some(match status.err {
+        Some(error) => Outcome::Failed {
+            slot: status.slot,
+            error,
+        },
+        None => Outcome::Landed { slot: status.slot },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use solana_sdk::instruction::InstructionError;
+    use solana_transaction_status::TransactionConfirmationStatus;
+
+    fn status(
+        err: Option<TransactionError>,
+        confirmation_status: TransactionConfirmationStatus,
+    ) -> TransactionStatus {
+        TransactionStatus {
+            slot: 10,
+            confirmations: None,
+            status: match &err {
+                Some(e) => Err(e.clone()),
+                None => Ok(()),
+            },
+            err,
+            confirmation_status: Some(confirmation_status),
+        }
+    }
+
+    #[test]
+    fn test_outcome() {
+        assert_eq!(
+            outcome(status(None, TransactionConfirmationStatus::Processed)),
+            None
+        );
+        assert_eq!(
+            outcome(status(None, TransactionConfirmationStatus::Confirmed)),
+            Some(Outcome::Landed { slot: 10 })
+        );
+
+        let error = TransactionError::InstructionError(7, InstructionError::Custom(0x1788));
+        assert_eq!(
+            outcome(status(
+                Some(error.clone()),
+                TransactionConfirmationStatus::Finalized
+            )),
+            Some(Outcome::Failed { slot: 10, error })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_resolve_emits_event() {
+        let tracker = ConfirmationTracker::new(Duration::from_secs(1));
+        let mut rx = tracker.subscribe();
+
+        let signature = Signature::new_unique();
+        tracker.track(signature, "rpc");
+        assert_eq!(tracker.pending_count(), 1);
+
+        tracker.resolve(&signature, Outcome::Landed { slot: 1 });
+        // 重复的结果不会再次发送事件
+        tracker.resolve(&signature, Outcome::Expired);
+        assert_eq!(tracker.pending_count(), 0);
+
+        let event = rx.recv().await.unwrap();
+        assert_eq!(event.signature, signature);
+        assert_eq!(event.label, "rpc");
+        assert_eq!(event.outcome, Outcome::Landed { slot: 1 });
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[test]
+    fn test_track_until() {
+        let tracker = ConfirmationTracker::new(Duration::from_secs(60));
+        let signature = Signature::new_unique();
+        tracker.track_until(signature, "rpc", Some(100));
+        // 重复登记不覆盖已设置的 last_valid_block_height
+        tracker.track(signature, "jito");
+
+        let pending = tracker.pending.lock().unwrap();
+        let pending = &pending[&signature];
+        assert_eq!(pending.label, "jito");
+        assert!(!pending.is_expired(Some(100), tracker.timeout));
+        assert!(pending.is_expired(Some(101), tracker.timeout));
+        // 未能获取区块高度时按超时时间判断
+        assert!(!pending.is_expired(None, tracker.timeout));
+        assert!(pending.is_expired(None, Duration::ZERO));
+    }
+}
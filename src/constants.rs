@@ -2,6 +2,9 @@ use solana_program::pubkey::Pubkey;
 use solana_program::pubkey::pubkey;
 
 pub static TX_SIZE: usize = 1232;
+/// Jito bundle 最多包含的交易数量
+pub const MAX_BUNDLE_TXS: usize = 5;
 pub const FEE_RECIPIENT_PUBKEY: Pubkey = pubkey!("ZYZhAvNcuF7AZnnP2yk66KZFSzrgYixzpidNcmxWYd7");
 pub const MEMO_PROGRAM_ID: Pubkey = pubkey!("MemoSq4gqABAXKb96qnH8TysNcWxMyWCqXgDLGmfcHr");
 pub const WSOL_MINT: Pubkey = pubkey!("So11111111111111111111111111111111111111112");
+pub const TOKEN_2022_PROGRAM_ID: Pubkey = pubkey!("TokenzQdBNbLqP5VEhdkAS6EPFLC1PHnBqCXEpPxuEb");
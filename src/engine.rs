@@ -1,17 +1,31 @@
+use crate::balance::{InputBalance, check_wrap_and_unwrap_sol, get_token_account_balance};
 use crate::blockhash::LatestBlockhash;
+use crate::broadcast::Broadcaster;
+use crate::bundle_simulation::{BundleSimulator, WatchedAccount};
 use crate::config::SwapConfig;
-use crate::flashloan::{FlashLoan, Kamino, NoFlashLoan};
+use crate::confirmation::{ConfirmationEvent, ConfirmationTracker, Outcome};
+use crate::flashloan::{FlashLoan, FlashLoanRegistry, Leverage, leverage};
 use crate::http_client::{HttpClient, IpSelectAlgorithm};
+use crate::jito::JitoRegions;
+use crate::leader::{LeaderTracker, Route, SLOT_DURATION};
+use crate::nonce::DurableNonce;
+use crate::quote_guard::QuoteGuard;
+use crate::race::RaceTracker;
+use crate::rate_limiter::BundleLimiter;
+use crate::rebroadcast::Rebroadcaster;
+use crate::sender::{HttpRelaySender, SendFuture, Sender};
+use crate::tpu::TpuSender;
+use crate::tx_builder::{self, ArbitrageInstructions, BatchCandidate};
 use crate::types::{
-    PrioritizationFeeLamports, PriorityLevelWithMaxLamports, QuoteResponse, QuoteReuqest, SwapData,
-    SwapRequest, SwapResponse,
+    EncodedInstruction, PrioritizationFeeLamports, PriorityLevelWithMaxLamports, QuoteResponse,
+    QuoteReuqest, SwapData, SwapRequest, SwapResponse,
 };
 use crate::{config, constants, error::SwapError, util};
 use anyhow::{Result, anyhow};
 use backoff::ExponentialBackoff;
 use backoff::future::retry;
 use base64::Engine as _;
-use jito_sdk_rust::{JitoJsonRpcSDK, http_client::IpSelectAlgorithm as JitoAlgorithm};
+use jito_sdk_rust::JitoJsonRpcSDK;
 use serde_json::json;
 use solana_client::{nonblocking::rpc_client::RpcClient, rpc_config::RpcSendTransactionConfig};
 use solana_program::address_lookup_table::state::AddressLookupTable;
@@ -19,21 +33,25 @@ use solana_sdk::{
     commitment_config::CommitmentConfig,
     instruction::AccountMeta,
     instruction::Instruction,
+    instruction::InstructionError,
     message::{AddressLookupTableAccount, VersionedMessage, v0::Message as V0Message},
     pubkey::Pubkey,
     signature::Keypair,
     signature::Signature,
     signer::Signer,
     timing::timestamp,
-    transaction::VersionedTransaction,
+    transaction::{TransactionError, VersionedTransaction},
 };
 use solana_sdk_ids::system_program;
 // use spl_associated_token_account::{
 //     get_associated_token_address, instruction::create_associated_token_account_idempotent,
 // };
-use std::{net::IpAddr, str::FromStr, sync::Arc};
+use std::{collections::HashSet, str::FromStr, sync::Arc};
 use tokio::{
-    sync::mpsc::{self, Receiver, Sender},
+    sync::{
+        broadcast::error::RecvError,
+        mpsc::{self, Receiver},
+    },
     time::{Duration, Instant, sleep},
 };
 use tracing::{Level, debug, error, info};
@@ -58,18 +76,462 @@ async fn load_alt_accounts(
     accounts
 }
 
+/// Jito bundle 提交相关的组件
+struct JitoSubmitter {
+    regions: Arc<JitoRegions>,
+    limiter: Arc<BundleLimiter>,
+    simulator: Option<BundleSimulator>,
+    /// bundle 状态检查通道
+    status_tx: mpsc::Sender<(String, Arc<JitoJsonRpcSDK>)>,
+    user_pubkey: Pubkey,
+}
+
+impl JitoSubmitter {
+    /// 模拟(可选)、限流后将交易打包成 bundle 提交
+    async fn submit(&self, txs: &[VersionedTransaction], profit: u64) -> Result<()> {
+        let config = config::get_config();
+        let user_pubkey = self.user_pubkey;
+
+        // 模拟交易DEBUG
+        // for tx in txs.iter() {
+        //     let sim = rpc_client.simulate_transaction(tx).await?;
+        //     println!("{:#?}", sim);
+        // }
+
+        // =========================== 打包交易
+        let encoded_txs: Vec<String> = txs
+            .iter()
+            .map(|tx| {
+                let encoded_tx = base64::engine::general_purpose::STANDARD
+                    .encode(bincode::serialize(&tx).unwrap());
+                encoded_tx
+            })
+            .collect();
+
+        // 提交前模拟 bundle，过滤掉失败或利润不足的 bundle
+        if let Some(simulator) = &self.simulator {
+            let watched = if config.swap.wrap_and_unwrap_sol {
+                WatchedAccount::Native(user_pubkey)
+            } else {
+                WatchedAccount::Token(spl_associated_token_account::get_associated_token_address(
+                    &user_pubkey,
+                    &constants::WSOL_MINT,
+                ))
+            };
+
+            let start_time = Instant::now();
+            let sim = simulator.simulate(txs, watched).await?;
+            debug!("simulateBundle elapsed_time: {:.4?}", start_time.elapsed());
+
+            if !sim.succeeded() {
+                for (idx, logs) in sim.logs().iter().enumerate() {
+                    debug!("交易{} logs = {:#?}", idx + 1, logs);
+                }
+                return Err(anyhow!(
+                    "❌ Bundle 模拟失败: {}",
+                    sim.error().cloned().unwrap_or_default()
+                ));
+            }
+
+            let simulated_profit = sim.profit().unwrap_or_default();
+            debug!(
+                "Bundle 模拟成功，余额变化 {:?}，利润 {}",
+                sim.balances(),
+                simulated_profit
+            );
+            if simulated_profit < config.min_profit_threshold_amount as i64 {
+                return Err(anyhow!(
+                    "👾 Bundle 模拟利润 {} 低于阈值 {}，放弃提交",
+                    simulated_profit,
+                    config.min_profit_threshold_amount
+                ));
+            }
+        }
+
+        let transactions = json!(encoded_txs);
+        let params = json!([transactions, {"encoding": "base64"}]);
+
+        // 令牌不足时排队，优先发送利润更高的 bundle
+        self.limiter
+            .acquire(profit)
+            .await
+            .map_err(|e| anyhow!("⏳ {}", e))?;
+
+        match self.regions.send_bundle(params).await {
+            Ok(receipt) => {
+                println!(
+                    "✅ Bundle sent to JITO [{}] with UUID: {}",
+                    receipt.region.endpoint, receipt.bundle_id
+                );
+                for (idx, tx) in txs.iter().enumerate() {
+                    println!("✅ 交易{}: {:?}", idx + 1, tx.signatures[0]);
+                }
+
+                // 启用打包状态检测功能
+                if config.jito.bundle_statuses_checking {
+                    _ = self
+                        .status_tx
+                        .send((receipt.bundle_id, Arc::clone(&receipt.region.sdk)))
+                        .await;
+                }
+
+                Ok(())
+            }
+            Err(e) => Err(anyhow!("❌ Failed to get bundle UUID from response, {}", e)),
+        }
+    }
+}
+
+impl Sender for JitoSubmitter {
+    fn name(&self) -> &str {
+        config::SENDER_JITO
+    }
+
+    fn tip_instruction(&self, payer: &Pubkey) -> Option<Instruction> {
+        let fee_amount = self.tip_amount();
+        let tip_account = util::get_jito_tip_fee_account().ok()?;
+        debug!("Tips account: {}, amount: {}", tip_account, fee_amount);
+        Some(solana_sdk::system_instruction::transfer(
+            payer,
+            &tip_account,
+            fee_amount,
+        ))
+    }
+
+    fn tip_amount(&self) -> u64 {
+        // TODO 百分比计算小费方式
+        config::get_config().jito.fixed_tip_amount
+    }
+
+    // JITO Tip 与 Priority Fee 只设置一个，否则浪费CU
+    fn priority_fee(&self) -> bool {
+        false
+    }
+
+    fn supports_bundle(&self) -> bool {
+        true
+    }
+
+    fn send<'a>(&'a self, txs: &'a [VersionedTransaction], profit: u64) -> SendFuture<'a> {
+        Box::pin(self.submit(txs, profit))
+    }
+}
+
+/// 通过 RPC 提交单笔交易，配置了多个发送端点时并发广播
+struct RpcSender {
+    rpc_client: Arc<RpcClient>,
+    broadcaster: Option<Arc<Broadcaster>>,
+    latest_blockhash: Arc<LatestBlockhash>,
+    /// 跳过预检查时定期重发交易
+    rebroadcaster: Option<Arc<Rebroadcaster>>,
+    skip_preflight: bool,
+    /// 混合提交模式下 RPC 版本使用的 compute unit price
+    compute_unit_price: Option<u64>,
+}
+
+impl Sender for RpcSender {
+    fn name(&self) -> &str {
+        config::SENDER_RPC
+    }
+
+    fn tip_instruction(&self, _payer: &Pubkey) -> Option<Instruction> {
+        None
+    }
+
+    fn priority_fee(&self) -> bool {
+        true
+    }
+
+    fn compute_unit_price(&self) -> Option<u64> {
+        self.compute_unit_price
+    }
+
+    fn supports_bundle(&self) -> bool {
+        false
+    }
+
+    fn send<'a>(&'a self, txs: &'a [VersionedTransaction], _profit: u64) -> SendFuture<'a> {
+        Box::pin(async move {
+            if txs.len() != 1 {
+                return Err(anyhow!("RPC 只能提交单笔交易，当前 {} 笔", txs.len()));
+            }
+
+            // 普通提交
+            let size = bincode::serialize(&txs[0])?.len();
+            if size > constants::TX_SIZE {
+                return Err(anyhow!(
+                    "❌ 当前交易数据大小 {} 超出Solana允许的单笔交易大小 1232 字节",
+                    size
+                ));
+            }
+
+            let last_valid_block_height = self
+                .latest_blockhash
+                .last_valid_block_height(txs[0].message.recent_blockhash())
+                .await;
+            match send_transaction_with_options(
+                &self.rpc_client,
+                self.broadcaster.as_deref(),
+                &txs[0],
+                self.skip_preflight,
+                last_valid_block_height,
+            )
+            .await
+            {
+                Ok(signature) => {
+                    println!("✅ 成功发送交易: https://solscan.io/tx/{}\n", signature);
+                    // 交易确认由 Dispatcher 统一登记
+                    if let Some(rebroadcaster) = &self.rebroadcaster {
+                        rebroadcaster.spawn(txs[0].clone());
+                    }
+                    Ok(())
+                }
+                Err(e) => match extract_program_error(&e) {
+                    Some((ix, code)) => {
+                        if let Some(e) = SwapError::from_code(code) {
+                            anyhow::bail!(
+                                "❗ 指令 #{} 失败，错误码: {} (0x{:x})，{}",
+                                ix,
+                                code,
+                                code,
+                                e
+                            );
+                        }
+
+                        anyhow::bail!("指令 #{} 失败，错误码: {} (0x{:x})", ix, code, code);
+                    }
+                    None => {
+                        anyhow::bail!("❌ 交易失败: {}", e);
+                    }
+                },
+            }
+        })
+    }
+}
+
+/// 根据配置创建交易提交通道
+async fn build_senders(
+    rpc_client: Arc<RpcClient>,
+    broadcaster: Option<Arc<Broadcaster>>,
+    confirmations: Arc<ConfirmationTracker>,
+    latest_blockhash: Arc<LatestBlockhash>,
+    jito: Arc<JitoSubmitter>,
+) -> Result<Vec<Arc<dyn Sender>>> {
+    let config = config::get_config();
+
+    let rebroadcaster = (config.skip_preflight && config.rebroadcast.enabled).then(|| {
+        Rebroadcaster::new(
+            Arc::clone(&rpc_client),
+            broadcaster.clone(),
+            Arc::clone(&confirmations),
+            config.rebroadcast.interval_to_duration(),
+        )
+    });
+
+    let mut senders: Vec<Arc<dyn Sender>> = Vec::new();
+    for name in config.sender_names() {
+        let sender: Arc<dyn Sender> = match name.as_str() {
+            config::SENDER_RPC => Arc::new(RpcSender {
+                rpc_client: Arc::clone(&rpc_client),
+                broadcaster: broadcaster.clone(),
+                latest_blockhash: Arc::clone(&latest_blockhash),
+                rebroadcaster: rebroadcaster.clone(),
+                skip_preflight: config.skip_preflight,
+                compute_unit_price: (config.hybrid.enabled && config.hybrid.compute_unit_price > 0)
+                    .then_some(config.hybrid.compute_unit_price),
+            }),
+            config::SENDER_JITO => jito.clone(),
+            config::SENDER_TPU => Arc::new(
+                TpuSender::new(Arc::clone(&rpc_client), &config.rpc_endpoint, &config.tpu).await?,
+            ),
+            _ => {
+                let relay = config
+                    .relays
+                    .iter()
+                    .find(|relay| relay.name == name)
+                    .ok_or_else(|| anyhow!("未找到提交通道 {} 的配置", name))?;
+                Arc::new(HttpRelaySender::new(
+                    relay,
+                    reqwest::Client::new(),
+                    config.http_request_timeout_to_duration(),
+                )?)
+            }
+        };
+        senders.push(sender);
+    }
+
+    // 各通道的交易使用不同的小费、优先费，互不冲突，先上链的一笔不会使其它版本失效，
+    // 同一个套利可能多次上链。check_profit 比较的是提交前读取的余额，第一笔上链后
+    // 余额已经增加，后续版本即使亏损也能通过检查。共用同一个持久 nonce 时，
+    // 任意一个版本上链都会推进 nonce，其余版本随之失效
+    if senders.len() > 1 && !config.nonce.enabled {
+        if config.senders.is_empty() && config.hybrid.enabled {
+            return Err(anyhow!(
+                "混合提交(hybrid.enabled)必须启用持久 nonce(nonce.enabled)，否则 Jito 与 RPC 两个版本可能先后上链"
+            ));
+        }
+        return Err(anyhow!(
+            "配置了多个提交通道({})时必须启用持久 nonce(nonce.enabled)，否则同一个套利可能多次上链",
+            config.sender_names().join(", ")
+        ));
+    }
+    Ok(senders)
+}
+
+/// 提交通道及其调度
+struct Dispatcher {
+    senders: Vec<Arc<dyn Sender>>,
+    confirmations: Arc<ConfirmationTracker>,
+    latest_blockhash: Arc<LatestBlockhash>,
+    race: RaceTracker,
+    leaders: Option<Arc<LeaderTracker>>,
+    /// 等待 Jito Leader 后重新报价时再次检查报价是否过时
+    quote_guard: Arc<QuoteGuard>,
+    /// 等待 Jito Leader 后重新报价时重新选择闪电贷来源
+    flash_loans: Option<FlashLoanRegistry>,
+}
+
+impl Dispatcher {
+    /// 通过指定通道提交，成功后登记到交易确认跟踪服务
+    ///
+    /// bundle 中的交易原子执行，只跟踪最后一笔
+    async fn send(
+        &self,
+        sender: &dyn Sender,
+        txs: &[VersionedTransaction],
+        profit: u64,
+    ) -> Result<()> {
+        sender.send(txs, profit).await?;
+        if let Some(tx) = txs.last() {
+            let last_valid_block_height = self
+                .latest_blockhash
+                .last_valid_block_height(tx.message.recent_blockhash())
+                .await;
+            self.confirmations.track_until(
+                tx.signatures[0],
+                sender.name(),
+                last_valid_block_height,
+            );
+        }
+        Ok(())
+    }
+
+    /// 扣除各通道的小费后利润仍高于 min_profit_threshold_amount 的通道
+    fn profitable(senders: &[Arc<dyn Sender>], profit: u64) -> Vec<Arc<dyn Sender>> {
+        let threshold = config::get_config().min_profit_threshold_amount;
+        senders
+            .iter()
+            .filter(|sender| {
+                let net = profit.saturating_sub(sender.tip_amount());
+                if net <= threshold {
+                    info!(
+                        "👁️ [{}] 扣除小费 {} 后利润 {} 不足 {}，不通过该通道提交",
+                        sender.name(),
+                        sender.tip_amount(),
+                        net,
+                        threshold
+                    );
+                }
+                net > threshold
+            })
+            .cloned()
+            .collect()
+    }
+
+    /// 根据即将出块的 Leader 选择本次使用的提交通道
+    ///
+    /// Jito Leader 即将出块时使用全部通道；否则紧急的套利只走非 Jito 通道，
+    /// 非紧急的套利等待 Jito Leader 出块后再提交，由调用方等待并重新报价
+    fn schedule(&self, senders: &[Arc<dyn Sender>], profit: u64) -> Result<Scheduled> {
+        let now = |senders: Vec<Arc<dyn Sender>>| Scheduled {
+            senders,
+            delay_slots: 0,
+        };
+        let Some(leaders) = &self.leaders else {
+            return Ok(now(senders.to_vec()));
+        };
+        if !senders.iter().any(|s| s.name() == config::SENDER_JITO) {
+            return Ok(now(senders.to_vec()));
+        }
+
+        let fallback: Vec<Arc<dyn Sender>> = senders
+            .iter()
+            .filter(|s| s.name() != config::SENDER_JITO)
+            .cloned()
+            .collect();
+        // 0 表示不区分紧急的套利
+        let urgent_profit_amount = config::get_config().leader.urgent_profit_amount;
+        let urgent = urgent_profit_amount > 0 && profit >= urgent_profit_amount;
+
+        match leaders.route(urgent, !fallback.is_empty()) {
+            Route::Jito => Ok(now(senders.to_vec())),
+            Route::Fallback => {
+                debug!("当前 Leader 未运行 Jito，通过其它通道提交");
+                Ok(now(fallback))
+            }
+            Route::Delay(slots) => Ok(Scheduled {
+                senders: senders.to_vec(),
+                delay_slots: slots,
+            }),
+            Route::Drop => Err(anyhow!("👾 近期没有 Jito Leader，放弃提交")),
+        }
+    }
+}
+
+/// 本次提交使用的通道
+struct Scheduled {
+    senders: Vec<Arc<dyn Sender>>,
+    /// 等待多少个 slot 后再提交，等待期间报价及 blockhash 都会过时
+    delay_slots: u64,
+}
+
+impl Scheduled {
+    async fn wait(&self) {
+        if self.delay_slots > 0 {
+            debug!("等待 {} 个 slot 后通过 Jito 提交", self.delay_slots);
+            sleep(SLOT_DURATION * self.delay_slots as u32).await;
+        }
+    }
+}
+
+/// 已获取全部指令、尚未签名的套利交易
+struct PreparedArbitrage {
+    ixs: ArbitrageInstructions,
+    flashloan: Option<Box<dyn FlashLoan>>,
+    alts: Vec<AddressLookupTableAccount>,
+    alts_2: Vec<AddressLookupTableAccount>,
+    recent_blockhash: solana_hash::Hash,
+    /// 使用持久 nonce 时的 nonce 值，只用于第一笔交易
+    nonce_hash: Option<solana_hash::Hash>,
+    /// 不含用户自身账户的可写账户
+    write_set: HashSet<Pubkey>,
+    profit: u64,
+    /// 利润检查账户在套利前的余额
+    balance: u64,
+    /// 利润检查要求的最低利润，包含闪电贷费用
+    min_profit: u64,
+}
+
 // #[derive(Debug)]
 pub struct Engine {
     http_client: HttpClient,
-    swap_channel_tx: Sender<SwapData>,
+    rpc_client: Arc<RpcClient>,
+    user_pubkey: Pubkey,
+    swap_channel_tx: mpsc::Sender<SwapData>,
+    lastest_blockhash: Arc<LatestBlockhash>,
+    quote_guard: Arc<QuoteGuard>,
+    flash_loans: Option<FlashLoanRegistry>,
+    /// 可用作本金的 input_mint 余额，只在启用闪电贷时读取
+    input_balance: Option<Arc<InputBalance>>,
+    leverage: Option<Leverage>,
 }
 
 impl Engine {
-    pub async fn new() -> Self {
+    pub async fn new() -> Result<Self> {
         let config = config::get_config();
 
-        let ip_pool = util::parse_ipv4_string(&config.ips).unwrap();
-        let http_client = HttpClient::initialize(ip_pool, IpSelectAlgorithm::RoundRobin).unwrap();
+        let ip_pool = util::parse_ipv4_string(&config.ips)?;
+        let http_client = HttpClient::initialize(ip_pool, IpSelectAlgorithm::RoundRobin)?;
 
         let rpc_endpoint = config.rpc_endpoint.clone();
         let rpc_client = Arc::new(RpcClient::new_with_commitment(
@@ -79,27 +541,85 @@ impl Engine {
 
         let (tx, rx) = mpsc::channel(100);
 
+        // 闪电贷来源只加载一次，之后由后台任务刷新。加载失败的来源被忽略，全部失败时返回错误
+        // 余额同样由后台任务刷新，每次套利只读取缓存
+        let (flash_loans, input_balance) = match &config.flash_loan {
+            Some(fl) => {
+                check_wrap_and_unwrap_sol(config.swap.wrap_and_unwrap_sol)?;
+                let input_mint = Pubkey::from_str(&config.swap.input_mint)
+                    .map_err(|e| anyhow!("INPUT_MINT {} 无效: {}", config.swap.input_mint, e))?;
+                let registry = FlashLoanRegistry::start(
+                    fl,
+                    rpc_client.clone(),
+                    config.keypair().pubkey(),
+                    input_mint,
+                )
+                .await?;
+                let input_balance = InputBalance::start(
+                    rpc_client.clone(),
+                    config.keypair().pubkey(),
+                    input_mint,
+                    fl.refresh_interval_to_duration(),
+                )
+                .await?;
+                (Some(registry), Some(input_balance))
+            }
+            None => (None, None),
+        };
+        let quote_guard = Arc::new(QuoteGuard::new(&config.quote_guard));
+
         // daemon
-        Engine::daemon_processor(rpc_client.clone(), http_client.clone(), rx).await;
+        let lastest_blockhash = Engine::daemon_processor(
+            rpc_client.clone(),
+            http_client.clone(),
+            rx,
+            Arc::clone(&quote_guard),
+            flash_loans.clone(),
+        )
+        .await?;
 
-        Self {
+        Ok(Self {
             http_client,
+            rpc_client,
+            user_pubkey: config.keypair().pubkey(),
             swap_channel_tx: tx,
-        }
+            lastest_blockhash,
+            quote_guard,
+            flash_loans,
+            input_balance,
+            leverage: config
+                .flash_loan
+                .as_ref()
+                .filter(|fl| fl.leverage.enabled)
+                .map(|fl| Leverage::new(&fl.leverage, config.swap.input_amount)),
+        })
     }
 
     async fn daemon_processor(
         rpc_client: Arc<RpcClient>,
         http_client: HttpClient,
         mut rx: Receiver<SwapData>,
-    ) {
+        quote_guard: Arc<QuoteGuard>,
+        flash_loans: Option<FlashLoanRegistry>,
+    ) -> Result<Arc<LatestBlockhash>> {
         let config = config::get_config();
         let payer = Arc::new(config.keypair());
         let user_pubkey = payer.pubkey();
 
         let rpc_endpoint = config.rpc_endpoint.clone();
 
-        let lastest_blockhash = LatestBlockhash::start(rpc_client.clone()).await;
+        let websocket_url = match &config.blockhash.websocket_url {
+            Some(url) => Some(url.clone()),
+            None => util::websocket_url(&rpc_endpoint)
+                .inspect_err(|e| error!("{}，改为轮询 blockhash", e))
+                .ok(),
+        };
+        let lastest_blockhash = LatestBlockhash::start(
+            rpc_client.clone(),
+            websocket_url,
+            config.blockhash.poll_interval_to_duration(),
+        )
+        .await;
 
         let balance: u64;
         // WELCOME
@@ -113,32 +633,30 @@ impl Engine {
             } = config.swap.clone();
 
             if input_mint.eq(&output_mint.clone()) {
-                panic!("INPUT_MINT must not be equal to OUTPUT_MINT ")
+                return Err(anyhow!("INPUT_MINT must not be equal to OUTPUT_MINT "));
             }
-            assert!(
-                util::check_mint_address(&rpc_client.clone(), &input_mint)
-                    .await
-                    .is_ok(),
-                "INPUT_MINT 无效"
-            );
-            assert!(
-                util::check_mint_address(&rpc_client.clone(), &output_mint)
-                    .await
-                    .is_ok(),
-                "OUTPUT_MINT 无效"
-            );
+            util::check_mint_address(&rpc_client.clone(), &input_mint)
+                .await
+                .map_err(|e| anyhow!("INPUT_MINT 无效: {}", e))?;
+            util::check_mint_address(&rpc_client.clone(), &output_mint)
+                .await
+                .map_err(|e| anyhow!("OUTPUT_MINT 无效: {}", e))?;
 
             println!("Current Configuration Information");
             println!("  钱包地址: {}", user_pubkey);
 
-            balance = rpc_client.get_balance(&user_pubkey).await.unwrap();
+            balance = rpc_client
+                .get_balance(&user_pubkey)
+                .await
+                .map_err(|e| anyhow!("获取钱包 {} 余额失败: {}", user_pubkey, e))?;
             println!("  钱包余额: {}", balance);
             println!(
                 "  闪电贷：{}",
-                if config.flash_loan.is_some() {
-                    "启用"
-                } else {
-                    "禁用"
+                match &config.flash_loan {
+                    Some(fl) if fl.leverage.enabled =>
+                        format!("启用({} 个来源，杠杆模式)", fl.all_sources().count()),
+                    Some(fl) => format!("启用({} 个来源)", fl.all_sources().count()),
+                    None => "禁用".to_string(),
                 }
             );
             println!("  INPUT_MINT: {}", input_mint);
@@ -163,8 +681,8 @@ impl Engine {
                 "  利润阈值: {} Lamports (不含任何优先费或Jito Tip)",
                 config.min_profit_threshold_amount
             );
-            println!("  启用 Jito Bundle 提交: {}", config.jito.bundle_submit);
-            if config.jito.bundle_submit {
+            println!("  交易提交通道: {}", config.sender_names().join(", "));
+            if config.jito_enabled() {
                 println!(
                     "       Jito Tip 计算方式: {}",
                     if config.jito.tip_rate_enabled {
@@ -174,7 +692,25 @@ impl Engine {
                     }
                 );
 
-                println!("       Endpoint Base URL: {}", config.jito.rpc_endpoint);
+                println!(
+                    "       Jito 认证: {}",
+                    if config.jito.uuid.is_some() {
+                        "UUID"
+                    } else {
+                        "匿名"
+                    }
+                );
+                if config.jito.rate_limit > 0.0 {
+                    println!("       Bundle 限流: {} 个/秒", config.jito.rate_limit);
+                }
+                println!(
+                    "       Bundle 模拟: {}",
+                    config.jito.simulation_endpoint.as_deref().unwrap_or("禁用")
+                );
+                println!("       区域选择: {}", config.jito.region_mode);
+                for endpoint in config.jito.endpoints() {
+                    println!("       Endpoint Base URL: {}", endpoint);
+                }
                 if config.jito.tip_rate_enabled {
                     println!("      百分比: {} % (利润)", config.jito.tip_rate_enabled,);
                     println!("       最大小费: {} Lamports", config.jito.max_tip_amount);
@@ -191,34 +727,37 @@ impl Engine {
         // 余额检查
         if !config.simulate_transaction {
             if balance == 0 {
-                panic!("账户 {} 余额不足! {} lamports", &user_pubkey, balance);
+                return Err(anyhow!(
+                    "账户 {} 余额不足! {} lamports",
+                    &user_pubkey,
+                    balance
+                ));
             }
         }
 
-        let jsdk = if !config.ips.is_empty() {
-            let ip_pool = util::parse_ipv4_string(&config.ips).unwrap();
+        if !config.ips.is_empty() {
             info!("Jito IP Pool Enabled");
-            JitoJsonRpcSDK::new_with_ip_pool(
-                &config.jito.rpc_endpoint,
-                None,
-                ip_pool.into_iter().map(IpAddr::V4).collect(),
-                JitoAlgorithm::Random,
-            )
-            .unwrap()
-        } else {
-            JitoJsonRpcSDK::new(&config.jito.rpc_endpoint, None)
-        };
-        let jito_sdk = Arc::new(jsdk);
+        }
+        let jito_regions = JitoRegions::new(&config.jito, &config.ips)?;
+        if config.jito_enabled() {
+            jito_regions.start_probe(config.jito.region_probe_interval_to_duration());
+        }
 
-        // [线程] jito bundle_id 状态检查
-        let (jito_tx, mut jito_rx) = tokio::sync::mpsc::channel::<String>(1000);
-        if config.jito.bundle_submit && config.jito.bundle_statuses_checking {
-            let jito_sdk_clone = Arc::clone(&jito_sdk);
+        let bundle_limiter = BundleLimiter::new(
+            config.jito.rate_limit,
+            config.jito.rate_limit_burst,
+            config.jito.max_pending_bundles,
+            config.jito.max_pending_to_duration(),
+        );
+
+        // [线程] jito bundle_id 状态检查，需要到接收 bundle 的区域查询
+        let (jito_tx, mut jito_rx) =
+            tokio::sync::mpsc::channel::<(String, Arc<JitoJsonRpcSDK>)>(1000);
+        if config.jito_enabled() && config.jito.bundle_statuses_checking {
             tokio::spawn(async move {
-                while let Some(bundle_uuid) = jito_rx.recv().await {
-                    let jito_sdk_clone = Arc::clone(&jito_sdk_clone);
+                while let Some((bundle_uuid, jito_sdk)) = jito_rx.recv().await {
                     tokio::spawn(async move {
-                        match check_bundle_id_status(&jito_sdk_clone, &bundle_uuid).await {
+                        match check_bundle_id_status(&jito_sdk, &bundle_uuid).await {
                             Ok(ret) => println!("👌 {:?} [{:?}]", bundle_uuid, ret),
                             Err(e) => println!("{}", e),
                         }
@@ -227,32 +766,181 @@ impl Engine {
             });
         }
 
+        let bundle_simulator = config.jito.simulation_endpoint.as_ref().map(|endpoint| {
+            BundleSimulator::new(
+                reqwest::Client::new(),
+                endpoint.clone(),
+                config.http_request_timeout_to_duration(),
+            )
+        });
+
+        let jito_submitter = Arc::new(JitoSubmitter {
+            regions: jito_regions,
+            limiter: bundle_limiter,
+            simulator: bundle_simulator,
+            status_tx: jito_tx,
+            user_pubkey,
+        });
+
+        let broadcaster = Broadcaster::new(&config.broadcast);
+        if let Some(broadcaster) = &broadcaster {
+            info!("交易广播端点: {} 个", broadcaster.endpoints().len());
+        }
+
+        let nonce = if config.nonce.enabled {
+            let account = Pubkey::from_str(&config.nonce.account)
+                .map_err(|e| anyhow!("nonce 账户 {} 无效: {}", config.nonce.account, e))?;
+            let nonce = DurableNonce::start(
+                rpc_client.clone(),
+                account,
+                user_pubkey,
+                config.nonce.refresh_interval_to_duration(),
+            )
+            .await?;
+            info!("使用持久 nonce 账户: {}", account);
+            Some(nonce)
+        } else {
+            None
+        };
+
+        let confirmations = ConfirmationTracker::start(
+            rpc_client.clone(),
+            config.confirmation.poll_interval_to_duration(),
+            config.confirmation.timeout_to_duration(),
+        );
+        {
+            let mut events = confirmations.subscribe();
+            let broadcaster = broadcaster.clone();
+            let confirmations = Arc::clone(&confirmations);
+            let nonce = nonce.clone();
+            tokio::spawn(async move {
+                loop {
+                    match events.recv().await {
+                        Ok(event) => {
+                            log_confirmation(&event);
+                            if let Some(broadcaster) = &broadcaster {
+                                broadcaster.on_confirmation(&event, confirmations.block_height());
+                            }
+                            if let Some(nonce) = &nonce {
+                                nonce.on_confirmation(&event);
+                            }
+                        }
+                        Err(RecvError::Lagged(n)) => {
+                            error!("交易确认事件处理过慢，丢失 {} 条", n);
+                        }
+                        Err(RecvError::Closed) => break,
+                    }
+                }
+            });
+        }
+
+        let senders = build_senders(
+            rpc_client.clone(),
+            broadcaster,
+            Arc::clone(&confirmations),
+            lastest_blockhash.clone(),
+            jito_submitter,
+        )
+        .await?;
+
+        let leaders = if config.leader.enabled {
+            Some(
+                LeaderTracker::start(
+                    rpc_client.clone(),
+                    lastest_blockhash.clone(),
+                    &config.leader,
+                )
+                .await?,
+            )
+        } else {
+            None
+        };
+
+        let dispatcher = Arc::new(Dispatcher {
+            senders,
+            confirmations,
+            latest_blockhash: Arc::clone(&lastest_blockhash),
+            race: RaceTracker::new(),
+            leaders,
+            quote_guard,
+            flash_loans,
+        });
+
         info!("后台处理线程已启动...");
-        let jito_sdk_clone = Arc::clone(&jito_sdk);
+        let blockhash_tracker = Arc::clone(&lastest_blockhash);
         let lastest_blockhash = lastest_blockhash.clone();
         // [线程] 创建一个后台任务持续处理消息
         tokio::spawn(async move {
+            // 批量模式：在时间窗口内收集多个套利机会，合并到一个 bundle，
+            // 只在唯一的提交通道支持 bundle 时生效。同一个 nonce 只能被一笔交易使用，
+            // 使用持久 nonce 时不合并
+            let batch_window = config.jito.batch_window_to_duration();
+            let batch_sender = match dispatcher.senders.as_slice() {
+                [sender] if sender.supports_bundle() && nonce.is_none() => Some(Arc::clone(sender)),
+                _ => None,
+            };
+            if let Some(batch_sender) =
+                batch_sender.filter(|_| !config.simulate_transaction && !batch_window.is_zero())
+            {
+                while let Some(first) = rx.recv().await {
+                    let mut batch = vec![first];
+                    let deadline = Instant::now() + batch_window;
+                    while batch.len() < constants::MAX_BUNDLE_TXS {
+                        match tokio::time::timeout_at(deadline, rx.recv()).await {
+                            Ok(Some(data)) => batch.push(data),
+                            _ => break,
+                        }
+                    }
+
+                    let start_time = Instant::now();
+                    let http_client = http_client.clone();
+                    let rpc_client = rpc_client.clone();
+                    let batch_sender = Arc::clone(&batch_sender);
+                    let dispatcher = Arc::clone(&dispatcher);
+                    let payer = payer.clone();
+                    let lastest_blockhash = lastest_blockhash.clone();
+                    tokio::spawn(async move {
+                        if let Err(e) = Engine::send_batch(
+                            http_client,
+                            rpc_client,
+                            &dispatcher,
+                            batch_sender,
+                            batch,
+                            user_pubkey,
+                            payer,
+                            lastest_blockhash,
+                        )
+                        .await
+                        {
+                            error!("{}", e)
+                        }
+                        debug!("⏱️ batch slapsed_time : {:.4?}", start_time.elapsed());
+                    });
+                }
+                return;
+            }
+
             // 循环接收消息直到通道关闭
             while let Some(data) = rx.recv().await {
                 // debug!("quote_response = {:#?}", &data);
-                let jito_tx_clone = jito_tx.clone();
                 let http_client = http_client.clone();
                 let rpc_client = rpc_client.clone();
                 let payer = payer.clone();
                 let lastest_blockhash = lastest_blockhash.clone();
+                let nonce = nonce.clone();
 
-                let jito_sdk_clone = Arc::clone(&jito_sdk_clone);
+                let dispatcher = Arc::clone(&dispatcher);
                 tokio::spawn(async move {
                     let start_time = Instant::now();
                     match Engine::send_transaction(
                         http_client,
                         Arc::clone(&rpc_client),
-                        &jito_sdk_clone,
-                        jito_tx_clone,
+                        dispatcher,
                         data,
                         user_pubkey.clone(),
                         &payer,
                         lastest_blockhash,
+                        nonce,
                     )
                     .await
                     {
@@ -269,38 +957,64 @@ impl Engine {
 
             // println!("后台处理线程成功退出...");
         });
+
+        Ok(blockhash_tracker)
     }
 
     async fn send_transaction(
         http_client: HttpClient,
         rpc_client: Arc<RpcClient>,
-        jito_sdk: &JitoJsonRpcSDK,
-        jito_tx: Sender<String>,
+        dispatcher: Arc<Dispatcher>,
         data: SwapData,
         user_pubkey: Pubkey,
         payer: &Keypair,
         lastest_blockhash: Arc<LatestBlockhash>,
+        nonce: Option<Arc<DurableNonce>>,
     ) -> Result<()> {
-        let txs = match Engine::build_tx(
+        let config = config::get_config();
+
+        // 先确定提交通道再获取 swap 指令及 blockhash，需要等待 Jito Leader 时等待后重新报价
+        let (data, senders) = if config.simulate_transaction {
+            (data, Vec::new())
+        } else {
+            let senders = Dispatcher::profitable(&dispatcher.senders, data.profit);
+            if senders.is_empty() {
+                return Err(anyhow!("👾 扣除小费后利润不足，放弃提交"));
+            }
+            let scheduled = dispatcher.schedule(&senders, data.profit)?;
+            scheduled.wait().await;
+            if scheduled.delay_slots == 0 {
+                (data, scheduled.senders)
+            } else {
+                let data = Engine::requote(&http_client, &dispatcher, data).await?;
+                let senders = Dispatcher::profitable(&scheduled.senders, data.profit);
+                if senders.is_empty() {
+                    return Err(anyhow!("👾 扣除小费后利润不足，放弃提交"));
+                }
+                (data, senders)
+            }
+        };
+
+        let prepared = Engine::prepare_arbitrage(
             &http_client,
             data,
             user_pubkey,
             &payer,
             Arc::clone(&rpc_client),
             lastest_blockhash,
+            nonce.as_deref(),
         )
-        .await
-        {
-            Ok(txs) => txs,
-            Err(e) => {
-                return Err(e);
-            }
-        };
-
-        let config = config::get_config();
+        .await?;
+        let profit = prepared.profit;
 
         // 交易大小检查
         if config.simulate_transaction {
+            let sender = dispatcher
+                .senders
+                .first()
+                .ok_or_else(|| anyhow!("未配置交易提交通道"))?;
+            let txs =
+                Engine::compile_arbitrage(&user_pubkey, payer, &prepared, sender.as_ref(), true)?;
             if txs.is_empty() {
                 return Err(anyhow!("未发现任何交易"));
             }
@@ -327,109 +1041,91 @@ impl Engine {
                 }
             }
             return Ok(());
-        } else {
-            // JITO bundle
-            if config.jito.bundle_submit {
-                // 模拟交易DEBUG
-                // for tx in txs.iter() {
-                //     let sim = rpc_client.simulate_transaction(tx).await?;
-                //     println!("{:#?}", sim);
-                // }
-
-                // =========================== 打包交易
-                let encoded_txs: Vec<String> = txs
-                    .iter()
-                    .map(|tx| {
-                        let encoded_tx = base64::engine::general_purpose::STANDARD
-                            .encode(bincode::serialize(&tx).unwrap());
-                        encoded_tx
-                    })
-                    .collect();
-
-                let transactions = json!(encoded_txs);
-                let params = json!([transactions, {"encoding": "base64"}]);
-
-                match jito_sdk.send_bundle(Some(params), None).await {
-                    Ok(res) => {
-                        if res.get("result").is_some() {
-                            println!("✅ Bundle sent to JITO with UUID: {}", res["result"]);
-                            for (idx, tx) in txs.iter().enumerate() {
-                                println!("✅ 交易{}: {:?}", idx + 1, tx.signatures[0]);
-                            }
+        }
 
-                            // 启用打包状态检测功能
-                            if config.jito.bundle_statuses_checking {
-                                _ = jito_tx.send(res["result"].to_string()).await;
-                            }
+        // 每个提交通道的小费、优先费要求不同，分别生成交易
+        let mut versions = Vec::with_capacity(senders.len());
+        for sender in &senders {
+            match Engine::compile_arbitrage(&user_pubkey, payer, &prepared, sender.as_ref(), true) {
+                Ok(txs) => versions.push((Arc::clone(sender), txs)),
+                Err(e) => error!("[{}] {}", sender.name(), e),
+            }
+        }
+        drop(prepared);
+
+        // 多个通道的交易推进同一个持久 nonce，最多只有一个能上链，记录胜出的通道
+        if versions.len() > 1 {
+            let entries: Vec<(String, Signature)> = versions
+                .iter()
+                .filter_map(|(sender, txs)| {
+                    Some((sender.name().to_string(), *txs.last()?.signatures.first()?))
+                })
+                .collect();
+            let dispatcher = Arc::clone(&dispatcher);
+            tokio::spawn(async move {
+                dispatcher
+                    .race
+                    .track(&dispatcher.confirmations, entries)
+                    .await
+            });
+        }
 
-                            return Ok(());
-                        } else {
-                            return Err(anyhow!(
-                                "❌ Failed to get bundle UUID from response, {}",
-                                res
-                            ));
-                        }
-                    }
-                    Err(e) => {
-                        return Err(anyhow!("❌ Failed to get bundle UUID from response, {}", e));
-                    }
-                }
-            } else {
-                // 普通提交
-                let size = bincode::serialize(&txs[0])?.len();
-                if size > constants::TX_SIZE {
-                    return Err(anyhow!(
-                        "❌ 当前交易数据大小 {} 超出Solana允许的单笔交易大小 1232 字节",
-                        size
-                    ));
-                }
-                // debug!("tx_info = {:?}", &txs[0]);
-
-                // let sim = rpc_client.simulate_transaction(&txs[0]).await?;
-                // if let Some(e) = &sim.value.err {
-                //     println!("simulate_transaction Failed, Err: {}", e);
-                //     return Err(anyhow!("❌ 交易模拟失败\n {:#?}", sim));
-                // }
-
-                let skip_preflight = config.skip_preflight;
-                match send_transaction_with_options(&rpc_client, &txs[0], skip_preflight).await {
-                    Ok(signature) => {
-                        println!("✅ 成功发送交易: https://solscan.io/tx/{}\n", signature);
-                        return Ok(());
-                    }
-                    Err(e) => match extract_program_error(&e) {
-                        Some((ix, code)) => {
-                            if let Some(e) = SwapError::from_code(code) {
-                                anyhow::bail!(
-                                    "❗ 指令 #{} 失败，错误码: {} (0x{:x})，{}",
-                                    ix,
-                                    code,
-                                    code,
-                                    e
-                                );
-                            }
+        let mut set = tokio::task::JoinSet::new();
+        for (sender, txs) in versions {
+            let dispatcher = Arc::clone(&dispatcher);
+            set.spawn(async move {
+                let result = dispatcher.send(sender.as_ref(), &txs, profit).await;
+                (sender, result)
+            });
+        }
 
-                            anyhow::bail!("指令 #{} 失败，错误码: {} (0x{:x})", ix, code, code);
-                        }
-                        None => {
-                            anyhow::bail!("❌ 交易失败: {}", e);
-                        }
-                    },
-                }
+        let mut errors = Vec::new();
+        let mut sent = false;
+        while let Some(joined) = set.join_next().await {
+            let (sender, result) = joined?;
+            match result {
+                Ok(()) => sent = true,
+                Err(e) => errors.push(format!("[{}] {}", sender.name(), e)),
             }
         }
+
+        if !sent {
+            return Err(anyhow!("{}", errors.join("\n")));
+        }
+        for e in errors {
+            error!("{}", e);
+        }
+        Ok(())
     }
 
-    async fn build_tx(
+    /// 获取套利所需的全部指令
+    async fn prepare_arbitrage(
         http_client: &HttpClient,
         data: SwapData,
         user_pubkey: Pubkey,
         payer: &Keypair,
         rpc_client: Arc<RpcClient>,
         lastest_blockhash: Arc<LatestBlockhash>,
-    ) -> Result<Vec<VersionedTransaction>> {
-        let mut txs: Vec<VersionedTransaction> = vec![];
-        let recent_blockhash = lastest_blockhash.get_blockhash().await;
+        nonce: Option<&DurableNonce>,
+    ) -> Result<PreparedArbitrage> {
+        // 使用持久 nonce 时 nonce 不会过期，不检查 blockhash 是否过时
+        let recent_blockhash = match nonce {
+            Some(_) => lastest_blockhash.get_blockhash().await,
+            None => {
+                lastest_blockhash
+                    .get_fresh_blockhash(config::get_config().blockhash.max_age_to_duration())
+                    .await?
+            }
+        };
+        let (nonce_hash, advance_nonce_ix) = match nonce {
+            Some(nonce) => (
+                Some(nonce.get_nonce().await),
+                Some(nonce.advance_instruction()),
+            ),
+            None => (None, None),
+        };
+        let profit = data.profit;
+        let route_mints = route_mints(&[&data.data1, &data.data2]);
 
         let start_time = Instant::now();
         let client_1 = http_client.clone().get_client().await;
@@ -473,202 +1169,352 @@ impl Engine {
             start_time.elapsed()
         );
 
-        let mut all_instructions: Vec<Instruction> = Vec::with_capacity(
-            swap_response.compute_budget_instructions.len()
-                + swap_response.setup_instructions.len()
-                + swap_response.other_instructions.len()
-                + 2,
-        );
+        // 闪电贷来源已在发现利润时选定
+        let (flashloan, flashloan_fee): (Option<Box<dyn FlashLoan>>, u64) = match data.flashloan {
+            Some(selected) => (Some(selected.flashloan), selected.fee),
+            None => (None, 0),
+        };
 
-        // 小费
-        if config::get_config().jito.bundle_submit {
-            // TODO 百分比计算小费方式
-            let fee_amount = config::get_config().jito.fixed_tip_amount;
-            let tip_account = util::get_jito_tip_fee_account().await?;
-            debug!("Tips account: {}, amount: {}", tip_account, fee_amount);
-            let tip_tx =
-                solana_sdk::system_instruction::transfer(&payer.pubkey(), &tip_account, fee_amount);
-            all_instructions.push(tip_tx);
-        }
+        // 优先费指令在生成交易时按提交通道的要求决定是否保留
+        let compute_budget_ixs = |encoded_ixs: Vec<EncodedInstruction>| -> Vec<Instruction> {
+            encoded_ixs.into_iter().map(Instruction::from).collect()
+        };
 
-        // prepare for flashloan
-        let mut use_flashloan = false;
-        let (ipt_mint, ipt_amount) = {
-            let swap = config::get_config().swap.clone();
-            (
-                Pubkey::from_str(&swap.input_mint).unwrap(),
-                swap.input_amount,
-            )
+        // 创建 ATA 指令
+        // {
+        //     let ata = get_associated_token_address(&user_pubkey, &ipt_mint);
+        //     let ata_ix = create_associated_token_account_idempotent(
+        //         &payer.pubkey(),
+        //         &ata,
+        //         &ipt_mint,
+        //         &spl_token::id(),
+        //     );
+        //     all_instructions.push(ata_ix);
+        // }
+
+        // 备注指令
+        let memo_ix = {
+            let memo_string = format!("Memo-{}", timestamp());
+            let memo = memo_string.as_bytes();
+            build_memo(memo, &[&payer.pubkey()])
         };
 
-        // TODO：perf
-        let flashloan: Box<dyn FlashLoan> = if config::get_config().flash_loan.is_some() {
-            let kamino = Kamino::new(
-                rpc_client.clone(),
-                user_pubkey,
-                ipt_amount,
-                Pubkey::from_str(&config::get_config().flash_loan.as_ref().unwrap().reserve)
-                    .unwrap(),
-                ipt_mint,
-            )
-            .await;
-            Box::new(kamino)
-        } else {
-            Box::new(NoFlashLoan)
+        // 添加 check_profit 利润检查指令
+        let current_balance = get_sol_balance_based_on_mode(
+            rpc_client.clone(),
+            &user_pubkey,
+            config::get_config().swap.wrap_and_unwrap_sol,
+        )
+        .await?;
+        // 将闪电贷费用计算在内
+        let min_profit = config::get_config().min_profit_amount + flashloan_fee;
+        let check_profit_ix =
+            Engine::get_check_profit_ix(&payer, current_balance, min_profit).await;
+
+        // 小费在生成交易时按提交通道添加
+        let arbitrage_ixs = ArbitrageInstructions {
+            advance_nonce: advance_nonce_ix,
+            tip: None,
+            compute_budget: compute_budget_ixs(swap_response.compute_budget_instructions),
+            compute_budget_2: compute_budget_ixs(swap_response_2.compute_budget_instructions),
+            // 只处理 setup_instructions、swap_instruction 和 cleanup_instruction
+            leg1: swap_leg_instructions(
+                swap_response.setup_instructions,
+                swap_response.swap_instruction,
+                swap_response.cleanup_instruction,
+            ),
+            leg2: swap_leg_instructions(
+                swap_response_2.setup_instructions,
+                swap_response_2.swap_instruction,
+                swap_response_2.cleanup_instruction,
+            ),
+            memo: memo_ix,
+            check_profit: Some(check_profit_ix),
         };
 
-        // input tx
-        {
-            // JITO Tip 与 Priority Fee 只设置一个，否则浪费CU
-            if config::get_config().jito.bundle_submit {
-                // 排除 priority_fee 指令
-                if swap_response.compute_budget_instructions.len() > 0 {
-                    // https://github.com/solana-labs/solana/blob/master/sdk/src/compute_budget.rs#L25
-                    // ix.data.first() == Some(&0x03)
-                    let ixs = util::exclude_set_compute_unit_price_ixs(
-                        &swap_response.compute_budget_instructions,
+        // 不属于用户自身的可写账户，用于判断多个套利之间是否存在写锁冲突
+        let write_set = {
+            let mut user_accounts = HashSet::from([user_pubkey]);
+            for mint in &route_mints {
+                for token_program in [spl_token::id(), constants::TOKEN_2022_PROGRAM_ID] {
+                    user_accounts.insert(
+                        spl_associated_token_account::get_associated_token_address_with_program_id(
+                            &user_pubkey,
+                            mint,
+                            &token_program,
+                        ),
                     );
-                    all_instructions.extend(ixs);
                 }
-            } else {
-                all_instructions.extend(
-                    swap_response
-                        .compute_budget_instructions
-                        .into_iter()
-                        .map(Instruction::from),
-                );
             }
 
-            // 创建 ATA 指令
-            // {
-            //     let ata = get_associated_token_address(&user_pubkey, &ipt_mint);
-            //     let ata_ix = create_associated_token_account_idempotent(
-            //         &payer.pubkey(),
-            //         &ata,
-            //         &ipt_mint,
-            //         &spl_token::id(),
-            //     );
-            //     all_instructions.push(ata_ix);
-            // }
-
-            // flashloan borrow ix
-            {
-                if config::get_config().flash_loan.is_some() {
-                    // if current_balance < ipt_amount {
-                    debug!("⚡ 启用闪电贷，借款 {}", ipt_amount);
-                    let borrow_ix = flashloan.borrow(all_instructions.len() as u8).unwrap();
-                    all_instructions.push(borrow_ix);
-
-                    // 更新账户余额，这时将贷款金额计算在内，后面利润保护合约使用
-                    // current_balance = current_balance.checked_add(ipt_amount).unwrap();
-                    use_flashloan = true;
-                    // }
-                }
+            let mut ixs = arbitrage_ixs.leg1.clone();
+            ixs.extend(arbitrage_ixs.leg2.iter().cloned());
+            if let Some(flashloan) = &flashloan {
+                ixs.extend(flashloan.borrow(0, 0));
+                ixs.extend(flashloan.repay(0));
             }
+            tx_builder::write_locks(&ixs, &user_accounts)
+        };
 
-            all_instructions.extend(
-                swap_response
-                    .setup_instructions
-                    .into_iter()
-                    .map(Instruction::from),
-            );
+        Ok(PreparedArbitrage {
+            ixs: arbitrage_ixs,
+            flashloan,
+            alts,
+            alts_2,
+            recent_blockhash,
+            nonce_hash,
+            write_set,
+            profit,
+            balance: current_balance,
+            min_profit,
+        })
+    }
 
-            all_instructions.push(Instruction::from(swap_response.swap_instruction));
-            if let Some(cleanup_instruction) = swap_response.cleanup_instruction {
-                all_instructions.push(Instruction::from(cleanup_instruction));
-            }
+    /// 按提交通道的小费、优先费要求签名并生成交易，超出单笔交易大小且通道支持 bundle 时
+    /// 拆分为多笔交易
+    ///
+    /// standalone 为 false 时不支付小费也不检查利润，用于多个套利合并到同一个 bundle 的场景，
+    /// 由 bundle 最后的结算交易统一处理
+    fn compile_arbitrage(
+        user_pubkey: &Pubkey,
+        payer: &Keypair,
+        prepared: &PreparedArbitrage,
+        sender: &dyn Sender,
+        standalone: bool,
+    ) -> Result<Vec<VersionedTransaction>> {
+        let PreparedArbitrage {
+            ixs: arbitrage_ixs,
+            flashloan,
+            alts,
+            alts_2,
+            recent_blockhash,
+            nonce_hash,
+            ..
+        } = prepared;
+        // 推进 nonce 的交易使用 nonce 值，其余交易使用最新的 blockhash
+        let first_blockhash = nonce_hash.unwrap_or(*recent_blockhash);
+
+        let mut arbitrage_ixs = arbitrage_ixs.clone();
+        if !sender.priority_fee() {
+            arbitrage_ixs = arbitrage_ixs.without_compute_unit_price();
+        } else if let Some(price) = sender.compute_unit_price() {
+            arbitrage_ixs = arbitrage_ixs.with_compute_unit_price(price);
+        }
+        if standalone {
+            arbitrage_ixs.tip = sender.tip_instruction(&payer.pubkey());
+        } else {
+            arbitrage_ixs.check_profit = None;
         }
-
-        // 只处理 setup_instructions、swap_instruction 和 cleanup_instruction
-        // let swap_response_2 =
-        //     match Engine::fetch_swap_instructions(&http_client, data.data2, user_pubkey).await {
-        //         Ok(res) => res,
-        //         Err(e) => {
-        //             error!("fetch_swap_instructions error: {:?}", e);
-        //             return Err(e);
-        //         }
-        //     };
 
         // 试图合并成一笔交易
-        {
-            let swap_response_clone = swap_response_2.clone();
-            let mut all_instruction_clone = all_instructions;
-            all_instruction_clone.extend(
-                swap_response_clone
-                    .setup_instructions
-                    .into_iter()
-                    .map(Instruction::from),
-            );
-            all_instruction_clone.push(Instruction::from(swap_response_clone.swap_instruction));
-            if swap_response_clone.cleanup_instruction.is_some() {
-                all_instruction_clone.push(Instruction::from(
-                    swap_response_clone.cleanup_instruction.unwrap(),
-                ));
-            }
-            // all_instruction_clone.push(Instruction::from(swap_response_clone.cleanup_instruction));
-            //
-            // 备注指令
-            {
-                let memo_string = format!("Memo-{}", timestamp());
-                let memo = memo_string.as_bytes();
-                let memo_instruction = build_memo(memo, &[&payer.pubkey()]);
-                all_instruction_clone.push(memo_instruction);
-            }
+        let mut all_alts = alts.clone();
+        all_alts.extend(alts_2.clone());
+        let tx_simple = Engine::convert_versioned_transaction(
+            user_pubkey,
+            payer,
+            &arbitrage_ixs.merged(flashloan.as_deref()),
+            &all_alts,
+            first_blockhash,
+        )?;
+
+        let size = bincode::serialize(&tx_simple)?.len();
+        if size <= constants::TX_SIZE {
+            return Ok(vec![tx_simple]);
+        }
+
+        // 超出单笔交易大小，只有 bundle 可以保证多笔交易原子执行
+        if !sender.supports_bundle() {
+            return Err(anyhow!("交易过大({} 字节)，超出 1232 字节", size));
+        }
 
-            // flashloan repay ix
-            if use_flashloan {
-                all_instruction_clone.push(flashloan.repay().unwrap());
+        debug!(
+            "交易过大({} 字节)，拆分为两笔交易通过 {} bundle 提交",
+            size,
+            sender.name()
+        );
+        let (ixs1, ixs2) = arbitrage_ixs
+            .split(flashloan.as_deref())
+            .map_err(|e| anyhow!("交易过大({} 字节)，{}", size, e))?;
+
+        let mut txs = Vec::with_capacity(2);
+        for (ixs, alts, blockhash) in [
+            (ixs1, alts, first_blockhash),
+            (ixs2, alts_2, *recent_blockhash),
+        ] {
+            let tx =
+                Engine::convert_versioned_transaction(user_pubkey, payer, &ixs, alts, blockhash)?;
+
+            let size = bincode::serialize(&tx)?.len();
+            if size > constants::TX_SIZE {
+                return Err(anyhow!("拆分后的交易过大，超出 1232 字节 ({} 字节)", size));
             }
+            txs.push(tx);
+        }
 
-            // 添加 check_profit 利润检查指令
-            {
-                let current_balance = get_sol_balance_based_on_mode(
-                    rpc_client.clone(),
-                    &user_pubkey,
-                    config::get_config().swap.wrap_and_unwrap_sol,
+        Ok(txs)
+    }
+
+    /// 将同一时间窗口内的多个套利机会合并到一个 bundle 中，只支付一次小费
+    ///
+    /// 存在写锁冲突或超出 bundle 交易数量上限的套利单独提交
+    async fn send_batch(
+        http_client: HttpClient,
+        rpc_client: Arc<RpcClient>,
+        dispatcher: &Arc<Dispatcher>,
+        sender: Arc<dyn Sender>,
+        batch: Vec<SwapData>,
+        user_pubkey: Pubkey,
+        payer: Arc<Keypair>,
+        lastest_blockhash: Arc<LatestBlockhash>,
+    ) -> Result<()> {
+        // 批量模式只有 bundle 一个通道，近期没有 Jito Leader 时等待后重新报价，或直接放弃
+        let max_profit = batch
+            .iter()
+            .map(|data| data.profit)
+            .max()
+            .unwrap_or_default();
+        let scheduled = dispatcher.schedule(std::slice::from_ref(&sender), max_profit)?;
+        scheduled.wait().await;
+        let requote = scheduled.delay_slots > 0;
+
+        let mut handles = Vec::with_capacity(batch.len());
+        for data in batch {
+            let http_client = http_client.clone();
+            let rpc_client = Arc::clone(&rpc_client);
+            let payer = Arc::clone(&payer);
+            let lastest_blockhash = Arc::clone(&lastest_blockhash);
+            let dispatcher = Arc::clone(dispatcher);
+            handles.push(tokio::spawn(async move {
+                let data = if requote {
+                    Engine::requote(&http_client, &dispatcher, data).await?
+                } else {
+                    data
+                };
+                Engine::prepare_arbitrage(
+                    &http_client,
+                    data,
+                    user_pubkey,
+                    &payer,
+                    rpc_client,
+                    lastest_blockhash,
+                    None,
                 )
                 .await
-                .unwrap();
-
-                let mut min_profit_amount = config::get_config().min_profit_amount;
-                // 将闪电贷款利润计算在内
-                if use_flashloan {
-                    let fl = config::get_config().flash_loan.as_ref().unwrap();
-                    min_profit_amount += (fl.borrow_rate * 1_000_000_000 as f64) as u64;
-                }
+            }));
+        }
 
-                let check_profit_ix =
-                    Engine::get_check_profit_ix(&payer, current_balance, min_profit_amount).await;
-                all_instruction_clone.push(check_profit_ix);
+        let mut prepared = Vec::with_capacity(handles.len());
+        for handle in handles {
+            match handle.await? {
+                Ok(p) => prepared.push(p),
+                Err(e) => error!("{}", e),
             }
+        }
+        if prepared.is_empty() {
+            return Ok(());
+        }
 
-            // println!("ixs = {:?}", all_instruction_clone);
+        // 每个套利只生成一次不带小费及利润检查的交易，每个 bundle 最后追加一笔结算交易，
+        // 按 bundle 开始前的余额检查全部套利的利润之和并支付小费。
+        // 若每笔交易分别检查利润，后面的交易比较的仍是 bundle 开始前的余额，
+        // 前面套利的利润会被重复计算
+        let mut compiled: Vec<Option<Vec<VersionedTransaction>>> = prepared
+            .iter()
+            .map(|p| {
+                Engine::compile_arbitrage(&user_pubkey, &payer, p, sender.as_ref(), false)
+                    .inspect_err(|e| error!("{}", e))
+                    .ok()
+            })
+            .collect();
+        let candidates: Vec<BatchCandidate> = prepared
+            .iter()
+            .zip(&compiled)
+            .map(|(p, txs)| BatchCandidate {
+                profit: p.profit,
+                tx_count: txs.as_ref().map_or(usize::MAX, Vec::len),
+                write_set: &p.write_set,
+            })
+            .collect();
+        // 预留一笔结算交易
+        let selected = tx_builder::pack_bundle(&candidates, constants::MAX_BUNDLE_TXS - 1);
+
+        // 合并的 bundle 在前，其余套利单独组成 bundle
+        let mut groups = Vec::with_capacity(prepared.len());
+        if !selected.is_empty() {
+            groups.push(selected.clone());
+        }
+        groups.extend(
+            (0..prepared.len())
+                .filter(|idx| !selected.contains(idx) && compiled[*idx].is_some())
+                .map(|idx| vec![idx]),
+        );
 
-            // TODO
-            let mut alts_clone = alts.clone();
-            alts_clone.extend(alts_2.clone());
-            // 这里合并指令时，只使用了第一笔交易的 ALTS, 并没有使用第二笔交易的ALTS
-            let tx_simple = Engine::convert_versioned_transaction(
+        // 签名生成所有 bundle 后再统一提交
+        let mut bundles: Vec<(Vec<VersionedTransaction>, u64)> = Vec::with_capacity(groups.len());
+        for group in groups {
+            let mut txs = Vec::with_capacity(constants::MAX_BUNDLE_TXS);
+            let (mut profit, mut min_profit, mut balance) = (0, 0, 0);
+            for &idx in &group {
+                txs.extend(compiled[idx].take().unwrap_or_default());
+                profit += prepared[idx].profit;
+                min_profit += prepared[idx].min_profit;
+                // 各套利分别读取余额，取最大值保证不会低估
+                balance = u64::max(balance, prepared[idx].balance);
+            }
+            let settlement = Engine::compile_settlement(
                 &user_pubkey,
                 &payer,
-                &all_instruction_clone,
-                &alts_clone,
-                recent_blockhash,
+                sender.as_ref(),
+                balance,
+                min_profit,
+                prepared[group[0]].recent_blockhash,
             )
-            .await?;
+            .await;
+            match settlement {
+                Ok(tx) => txs.push(tx),
+                Err(e) => {
+                    error!("{}", e);
+                    continue;
+                }
+            }
+            if group.len() > 1 {
+                debug!(
+                    "📦 {} 个套利机会合并到一个 bundle，共 {} 笔交易",
+                    group.len(),
+                    txs.len()
+                );
+            }
+            bundles.push((txs, profit));
+        }
 
-            // 超出单笔交易大小
-            let size = bincode::serialize(&tx_simple)?.len();
-            if size > constants::TX_SIZE {
-                return Err(anyhow!("交易过大，超出 1232 字节"));
+        for (txs, profit) in bundles {
+            if Dispatcher::profitable(std::slice::from_ref(&sender), profit).is_empty() {
+                continue;
+            }
+            if let Err(e) = dispatcher.send(sender.as_ref(), &txs, profit).await {
+                error!("{}", e);
             }
-            txs.push(tx_simple);
         }
 
-        Ok(txs)
+        Ok(())
+    }
+
+    /// bundle 的结算交易：按 bundle 开始前的余额检查全部套利的利润之和，并支付小费
+    async fn compile_settlement(
+        user_pubkey: &Pubkey,
+        payer: &Keypair,
+        sender: &dyn Sender,
+        balance: u64,
+        min_profit: u64,
+        recent_blockhash: solana_hash::Hash,
+    ) -> Result<VersionedTransaction> {
+        let mut ixs = vec![Engine::get_check_profit_ix(payer, balance, min_profit).await];
+        // 小费放在最后，利润检查失败时整个 bundle 都不会上链
+        ixs.extend(sender.tip_instruction(&payer.pubkey()));
+        Engine::convert_versioned_transaction(user_pubkey, payer, &ixs, &vec![], recent_blockhash)
     }
 
-    async fn convert_versioned_transaction(
+    fn convert_versioned_transaction(
         user_pubkey: &Pubkey,
         payer: &Keypair,
         instructions: &Vec<Instruction>,
@@ -683,18 +1529,39 @@ impl Engine {
     }
 
     // #[instrument(skip(self), fields(request_id))]
+    /// 可用作本金的 input_mint 余额，读取后台刷新的缓存
+    fn input_balance(&self) -> u64 {
+        self.input_balance
+            .as_ref()
+            .map_or(0, |balance| balance.get())
+    }
+
+    /// 停止后台的 blockhash 跟踪，退出前调用
+    pub fn shutdown(&self) {
+        self.lastest_blockhash.shutdown();
+    }
+
     pub async fn run(&mut self) -> Result<()> {
         let config = config::get_config();
         let SwapConfig {
             input_mint,
             output_mint,
-            input_amount: quote_in_amount,
+            input_amount,
             slippage_bps,
             ..
         } = config.swap.clone();
 
         let start = Instant::now();
 
+        // 余额由后台任务刷新，每轮只读取一次缓存。杠杆模式下根据余额及可借流动性确定本轮的交易数量
+        let balance = self.input_balance();
+        let quote_in_amount = match (&self.leverage, &self.flash_loans) {
+            (Some(leverage), Some(registry)) => {
+                leverage.amount(balance, registry.available_liquidity())
+            }
+            _ => input_amount,
+        };
+
         let initial_interval = Duration::from_secs(5);
         let max_interval = Duration::from_secs(60);
         let multiplier = 1.5;
@@ -816,6 +1683,20 @@ impl Engine {
             return Ok(());
         }
 
+        // 根据价格影响调整下一轮的交易数量，本轮超出上限时放弃
+        if let Some(leverage) = self.leverage.as_mut() {
+            let price_impact = leverage::price_impact(&quote1, &quote2);
+            if leverage.observe(quote_in_amount, price_impact) {
+                debug!(
+                    "👁️ 交易数量 {} 的价格影响 {} 超出上限，下一轮调整为 {}",
+                    quote_in_amount,
+                    price_impact,
+                    leverage.target()
+                );
+                return Ok(());
+            }
+        }
+
         let diff = quote2_out_amount as i64 - quote_in_amount as i64;
         let snipe = diff > 0 && diff as u64 > config.min_profit_threshold_amount;
         debug!(
@@ -824,12 +1705,67 @@ impl Engine {
         );
 
         if snipe {
+            // 报价过时，池子状态可能已经改变
+            if let Err(rejection) = self.quote_guard.check(
+                self.lastest_blockhash.slot(),
+                quote1.context_slot,
+                quote2.context_slot,
+            ) {
+                let (stale, leg_span) = self.quote_guard.snapshot();
+                info!(
+                    "👁️ 放弃过时的报价: {}，累计拒绝: 报价落后 {} 次，报价相差过多 {} 次",
+                    rejection, stale, leg_span
+                );
+                return Ok(());
+            }
+
+            // 钱包余额不足 input_amount 时只借入差额，扣除借款费用后重新判断利润
+            let flashloan = match &self.flash_loans {
+                Some(registry) => {
+                    let shortfall = quote_in_amount.saturating_sub(balance);
+                    if shortfall == 0 {
+                        debug!("余额 {} 充足，无需借款", balance);
+                        None
+                    } else {
+                        match registry.select(shortfall) {
+                            Ok(selected) => {
+                                debug!(
+                                    "⚡ 余额 {}，通过闪电贷[{}]借款 {}，费用 {}",
+                                    balance, selected.source, shortfall, selected.fee
+                                );
+                                Some(selected)
+                            }
+                            Err(e) => {
+                                info!("👁️ 放弃套利: {}", e);
+                                if let Some(leverage) = self.leverage.as_mut() {
+                                    leverage.shrink(quote_in_amount);
+                                }
+                                return Ok(());
+                            }
+                        }
+                    }
+                }
+                None => None,
+            };
+            let flashloan_fee = flashloan.as_ref().map_or(0, |selected| selected.fee);
+            if flashloan_fee > 0
+                && diff as u64 <= config.min_profit_threshold_amount + flashloan_fee
+            {
+                info!(
+                    "👁️ 扣除闪电贷[{}]费用 {} 后利润不足，放弃",
+                    flashloan.as_ref().unwrap().source,
+                    flashloan_fee
+                );
+                return Ok(());
+            }
+            let diff = diff - flashloan_fee as i64;
+
             println!(
                 "🔥 发现利润：{quote2_out_amount} - {quote_in_amount} = {diff} Lamports ({} SOL) 🔥",
                 diff as f64 / 10f64.powi(9)
             );
 
-            if config.jito.bundle_submit {
+            if config.jito_enabled() {
                 let tip_amount = util::calculation_jito_tip_amount(diff);
                 println!(
                     "💵 启用 Jito Bundle Submit，本次支付小费 {}，可获得利润 {}",
@@ -859,6 +1795,8 @@ impl Engine {
                 .send(SwapData {
                     data1: quote1,
                     data2: quote2,
+                    profit: diff as u64,
+                    flashloan,
                 })
                 .await
             {
@@ -876,6 +1814,23 @@ impl Engine {
         output_mint: &str,
         amount: u64,
         slippage_bps: u64,
+    ) -> Result<QuoteResponse> {
+        Engine::fetch_quote(
+            &self.http_client,
+            input_mint,
+            output_mint,
+            amount,
+            slippage_bps,
+        )
+        .await
+    }
+
+    async fn fetch_quote(
+        http_client: &HttpClient,
+        input_mint: &str,
+        output_mint: &str,
+        amount: u64,
+        slippage_bps: u64,
     ) -> Result<QuoteResponse> {
         // SOL => USDT
         // ?inputMint={input}&outputMint={output}&amount={amount}&slippageBps=50
@@ -891,8 +1846,7 @@ impl Engine {
         };
 
         let start = Instant::now();
-        let resp = self
-            .http_client
+        let resp = http_client
             .get_client()
             .await
             .get(&url)
@@ -912,6 +1866,74 @@ impl Engine {
         Ok(quote)
     }
 
+    /// 等待 Jito Leader 出块后重新报价，等待期间池子状态可能已经改变
+    ///
+    /// 交易数量及借款数量不变。新报价同样需要通过过时检查，并按当前状态重新选择闪电贷来源，
+    /// 扣除新的闪电贷费用后利润不足时放弃
+    async fn requote(
+        http_client: &HttpClient,
+        dispatcher: &Dispatcher,
+        data: SwapData,
+    ) -> Result<SwapData> {
+        let config = config::get_config();
+        let slippage_bps = config.swap.slippage_bps;
+        let in_amount = data.data1.in_amount.parse::<u64>()?;
+        let quote1 = Engine::fetch_quote(
+            http_client,
+            &data.data1.input_mint,
+            &data.data1.output_mint,
+            in_amount,
+            slippage_bps,
+        )
+        .await?;
+        let quote2 = Engine::fetch_quote(
+            http_client,
+            &data.data2.input_mint,
+            &data.data2.output_mint,
+            quote1.out_amount.parse::<u64>()?,
+            slippage_bps,
+        )
+        .await?;
+
+        dispatcher
+            .quote_guard
+            .check(
+                dispatcher.latest_blockhash.slot(),
+                quote1.context_slot,
+                quote2.context_slot,
+            )
+            .map_err(|rejection| anyhow!("👁️ 重新报价后放弃过时的报价: {}", rejection))?;
+
+        // 等待期间可借数量及费用可能已经改变
+        let flashloan = match (data.flashloan, &dispatcher.flash_loans) {
+            (Some(selected), Some(registry)) => Some(
+                registry
+                    .select(selected.amount)
+                    .map_err(|e| anyhow!("👁️ 重新报价后放弃套利: {}", e))?,
+            ),
+            (selected, _) => selected,
+        };
+
+        let flashloan_fee = flashloan.as_ref().map_or(0, |selected| selected.fee);
+        let diff =
+            quote2.out_amount.parse::<u64>()? as i64 - in_amount as i64 - flashloan_fee as i64;
+        if diff <= config.min_profit_threshold_amount as i64 {
+            return Err(anyhow!(
+                "👁️ 重新报价后利润 {} 不足(原 {})，放弃",
+                diff,
+                data.profit
+            ));
+        }
+        debug!("重新报价后利润: {} (原 {})", diff, data.profit);
+
+        Ok(SwapData {
+            data1: quote1,
+            data2: quote2,
+            profit: diff as u64,
+            flashloan,
+        })
+    }
+
     async fn fetch_swap_instructions(
         http_client: &reqwest::Client,
         quote: QuoteResponse,
@@ -933,8 +1955,8 @@ impl Engine {
         // let jsona = serde_json::to_string_pretty(&swap_request).unwrap();
         // println!("{}", jsona);
 
-        // 普通交易
-        if !(config.jito.bundle_submit) {
+        // 有提交通道需要优先费时才请求优先费指令
+        if config.priority_fee_required() {
             let prioritization_fee = PrioritizationFeeLamports {
                 priority_level_with_max_lamports: Some(PriorityLevelWithMaxLamports {
                     priority_level: Some("high".to_string()),
@@ -1011,34 +2033,98 @@ impl Engine {
 
 async fn send_transaction_with_options(
     rpc_client: &RpcClient,
+    broadcaster: Option<&Broadcaster>,
     tx: &VersionedTransaction,
     skip_preflight: bool,
+    last_valid_block_height: Option<u64>,
 ) -> Result<Signature> {
-    if skip_preflight {
-        // 跳过预检查的模式
-        let config = RpcSendTransactionConfig {
-            skip_preflight: true,
-            preflight_commitment: None,
-            encoding: None,
-            max_retries: None,
-            min_context_slot: None,
-        };
+    // 只负责发送，交易确认由 ConfirmationTracker 在后台完成
+    let config = RpcSendTransactionConfig {
+        skip_preflight,
+        preflight_commitment: (!skip_preflight).then(|| rpc_client.commitment().commitment),
+        encoding: None,
+        max_retries: None,
+        min_context_slot: None,
+    };
+
+    // 配置了多个发送端点时并发广播
+    if let Some(broadcaster) = broadcaster {
+        let receipt = broadcaster
+            .broadcast(tx, config, last_valid_block_height)
+            .await?;
+        debug!(
+            "交易 {} 最先由 {} 接收",
+            receipt.signature, receipt.first_endpoint.url
+        );
+        return Ok(receipt.signature);
+    }
 
-        let signature = rpc_client.send_transaction_with_config(tx, config).await?;
+    rpc_client
+        .send_transaction_with_config(tx, config)
+        .await
+        .map_err(|e| anyhow!("{}", e))
+}
 
-        // 可以选择是否等待确认
-        let _confirmation = rpc_client
-            .confirm_transaction_with_commitment(&signature, CommitmentConfig::confirmed())
-            .await?;
+/// 输出交易确认结果，失败时解析程序错误码
+fn log_confirmation(event: &ConfirmationEvent) {
+    match &event.outcome {
+        Outcome::Landed { slot } => info!(
+            "✅ [{}] 交易已上链 slot: {}, 耗时 {:.2?}: https://solscan.io/tx/{}",
+            event.label, slot, event.elapsed, event.signature
+        ),
+        Outcome::Failed { slot, error } => {
+            let reason = match error {
+                TransactionError::InstructionError(ix, InstructionError::Custom(code)) => {
+                    match SwapError::from_code((*code).into()) {
+                        Some(e) => {
+                            format!("指令 #{} 失败，错误码: {} (0x{:x})，{}", ix, code, code, e)
+                        }
+                        None => format!("指令 #{} 失败，错误码: {} (0x{:x})", ix, code, code),
+                    }
+                }
+                _ => error.to_string(),
+            };
+            error!(
+                "❌ [{}] 交易执行失败 slot: {}, {}: https://solscan.io/tx/{}",
+                event.label, slot, reason, event.signature
+            );
+        }
+        Outcome::Expired => error!(
+            "⌛ [{}] 交易 {:.2?} 内未上链: {}",
+            event.label, event.elapsed, event.signature
+        ),
+    }
+}
 
-        Ok(signature)
-    } else {
-        // 使用默认的带预检查的方法
-        rpc_client
-            .send_and_confirm_transaction(tx)
-            .await
-            .map_err(|e| anyhow!("{}", e))
+/// 报价路由中涉及的所有 mint
+fn route_mints(quotes: &[&QuoteResponse]) -> HashSet<Pubkey> {
+    quotes
+        .iter()
+        .flat_map(|quote| {
+            quote
+                .route_plan
+                .iter()
+                .flat_map(|plan| [&plan.swap_info.input_mint, &plan.swap_info.output_mint])
+        })
+        .filter_map(|mint| Pubkey::from_str(mint).ok())
+        .collect()
+}
+
+/// 单个 swap 的指令: setup + swap + cleanup
+fn swap_leg_instructions(
+    setup_instructions: Vec<EncodedInstruction>,
+    swap_instruction: EncodedInstruction,
+    cleanup_instruction: Option<EncodedInstruction>,
+) -> Vec<Instruction> {
+    let mut ixs: Vec<Instruction> = setup_instructions
+        .into_iter()
+        .map(Instruction::from)
+        .collect();
+    ixs.push(Instruction::from(swap_instruction));
+    if let Some(cleanup_instruction) = cleanup_instruction {
+        ixs.push(Instruction::from(cleanup_instruction));
     }
+    ixs
 }
 
 /// 构建一个Memo指令
@@ -1272,17 +2358,8 @@ pub async fn get_wsol_balance(rpc_client: Arc<RpcClient>, user_pubkey: &Pubkey)
         &constants::WSOL_MINT,
     );
 
-    // 查询代币账户
-    match rpc_client
-        .get_token_account_balance(&associated_token_address)
-        .await
-    {
-        Ok(balance_response) => Ok(balance_response.amount.parse::<u64>().unwrap_or(0)),
-        Err(_) => {
-            // 账户不存在或查询失败，返回 0
-            Ok(0)
-        }
-    }
+    // 查询代币账户，账户不存在时余额为 0
+    get_token_account_balance(&rpc_client, &associated_token_address).await
 }
 
 // #[cfg(test)]
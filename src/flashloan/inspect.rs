@@ -0,0 +1,207 @@
+use super::{FRACTION_BITS, KaminoState};
+use serde::Serialize;
+use std::fmt;
+
+/// Kamino reserve 的借款相关信息，用于在写入配置前检查 reserve
+#[derive(Debug, Serialize)]
+pub struct ReserveReport {
+    pub reserve: String,
+    pub lending_market: String,
+    pub mint: String,
+    pub mint_decimals: u64,
+    pub token_program: String,
+    pub status: &'static str,
+    pub available_amount: u64,
+    pub borrowed_amount: u64,
+    pub deposit_limit: u64,
+    pub borrow_limit: u64,
+    pub borrow_limit_outside_elevation_group: u64,
+    /// 0 表示不限制
+    pub utilization_limit_pct: u8,
+    pub flash_loan_enabled: bool,
+    pub flash_loan_fee_sf: u64,
+    pub flash_loan_fee_bps: f64,
+    pub referral_fee_bps: u16,
+    pub emergency_mode: bool,
+    pub borrow_disabled: bool,
+    /// 借款前检查未通过的原因，None 表示可借款
+    pub unavailable_reason: Option<String>,
+    pub elevation_groups: Vec<ElevationGroupReport>,
+}
+
+/// reserve 所属的 elevation group
+#[derive(Debug, Serialize, PartialEq)]
+pub struct ElevationGroupReport {
+    pub id: u8,
+    pub ltv_pct: u8,
+    pub liquidation_threshold_pct: u8,
+    pub allow_new_loans: bool,
+    pub debt_reserve: String,
+    /// 以该 reserve 作为抵押时的借款上限
+    pub borrow_limit_against_collateral: u64,
+}
+
+impl From<&KaminoState> for ReserveReport {
+    fn from(state: &KaminoState) -> Self {
+        let reserve = &state.reserve;
+        let market = &state.lending_market;
+        let config = &reserve.config;
+        let fee_sf = config.fees.flash_loan_fee_sf;
+
+        // elevation group id 从 1 开始，0 表示未使用
+        let elevation_groups = config
+            .elevation_groups
+            .iter()
+            .filter(|id| **id != 0)
+            .filter_map(|id| {
+                let index = *id as usize - 1;
+                let group = market.elevation_groups.get(index)?;
+                Some(ElevationGroupReport {
+                    id: *id,
+                    ltv_pct: group.ltv_pct,
+                    liquidation_threshold_pct: group.liquidation_threshold_pct,
+                    allow_new_loans: group.allow_new_loans != 0,
+                    debt_reserve: group.debt_reserve.to_string(),
+                    borrow_limit_against_collateral: config
+                        .borrow_limit_against_this_collateral_in_elevation_group
+                        .get(index)
+                        .copied()
+                        .unwrap_or(0),
+                })
+            })
+            .collect();
+
+        Self {
+            reserve: state.reserve_pubkey.to_string(),
+            lending_market: reserve.lending_market.to_string(),
+            mint: reserve.liquidity.mint_pubkey.to_string(),
+            mint_decimals: reserve.liquidity.mint_decimals,
+            token_program: reserve.liquidity.token_program.to_string(),
+            status: match config.status {
+                0 => "active",
+                1 => "obsolete",
+                2 => "hidden",
+                _ => "unknown",
+            },
+            available_amount: reserve.liquidity.available_amount,
+            borrowed_amount: (reserve.liquidity.borrowed_amount_sf >> FRACTION_BITS) as u64,
+            deposit_limit: config.deposit_limit,
+            borrow_limit: config.borrow_limit,
+            borrow_limit_outside_elevation_group: config.borrow_limit_outside_elevation_group,
+            utilization_limit_pct: config.utilization_limit_block_borrowing_above_pct,
+            flash_loan_enabled: fee_sf != u64::MAX,
+            flash_loan_fee_sf: fee_sf,
+            flash_loan_fee_bps: fee_sf as f64 / (1u128 << FRACTION_BITS) as f64 * 10_000.0,
+            referral_fee_bps: market.referral_fee_bps,
+            emergency_mode: market.emergency_mode != 0,
+            borrow_disabled: market.borrow_disabled != 0,
+            unavailable_reason: state.check(0).err().map(|e| e.to_string()),
+            elevation_groups,
+        }
+    }
+}
+
+impl fmt::Display for ReserveReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "reserve: {}", self.reserve)?;
+        writeln!(f, "lending market: {}", self.lending_market)?;
+        writeln!(f, "mint: {} (decimals {})", self.mint, self.mint_decimals)?;
+        writeln!(f, "token program: {}", self.token_program)?;
+        writeln!(f, "状态: {}", self.status)?;
+        writeln!(f, "可借数量: {}", self.available_amount)?;
+        writeln!(f, "已借数量: {}", self.borrowed_amount)?;
+        writeln!(f, "存款上限: {}", self.deposit_limit)?;
+        writeln!(f, "借款上限: {}", self.borrow_limit)?;
+        writeln!(
+            f,
+            "elevation group 外借款上限: {}",
+            self.borrow_limit_outside_elevation_group
+        )?;
+        writeln!(f, "使用率上限: {}%", self.utilization_limit_pct)?;
+        if self.flash_loan_enabled {
+            writeln!(
+                f,
+                "闪电贷费率: {} bps (flash_loan_fee_sf = {})",
+                self.flash_loan_fee_bps, self.flash_loan_fee_sf
+            )?;
+        } else {
+            writeln!(f, "闪电贷费率: 已禁用")?;
+        }
+        writeln!(f, "推荐人分成: {} bps", self.referral_fee_bps)?;
+        writeln!(f, "紧急模式: {}", self.emergency_mode)?;
+        writeln!(f, "禁止借款: {}", self.borrow_disabled)?;
+        for group in &self.elevation_groups {
+            writeln!(
+                f,
+                "elevation group {}: ltv {}%，清算阈值 {}%，允许新借款 {}，借款资产 {}，抵押借款上限 {}",
+                group.id,
+                group.ltv_pct,
+                group.liquidation_threshold_pct,
+                group.allow_new_loans,
+                group.debt_reserve,
+                group.borrow_limit_against_collateral
+            )?;
+        }
+        match &self.unavailable_reason {
+            Some(reason) => write!(f, "⚠️  不可借款: {}", reason),
+            None => write!(f, "✅ 可借款"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{LendingMarket, Reserve, decode_account};
+    use super::*;
+    use solana_program::pubkey::Pubkey;
+
+    #[test]
+    fn test_reserve_report() {
+        let zeroed = vec![0u8; 16_384];
+        let mut reserve: Reserve = decode_account(&zeroed).unwrap();
+        let mut lending_market: LendingMarket = decode_account(&zeroed).unwrap();
+
+        reserve.liquidity.available_amount = 5_000;
+        reserve.liquidity.borrowed_amount_sf = 1_000u128 << FRACTION_BITS;
+        reserve.config.borrow_limit = 10_000;
+        reserve.config.fees.flash_loan_fee_sf = (1u64 << FRACTION_BITS) / 1_000;
+        reserve.config.elevation_groups[0] = 2;
+        reserve
+            .config
+            .borrow_limit_against_this_collateral_in_elevation_group[1] = 700;
+        let debt_reserve = Pubkey::new_unique();
+        lending_market.elevation_groups[1].id = 2;
+        lending_market.elevation_groups[1].ltv_pct = 90;
+        lending_market.elevation_groups[1].allow_new_loans = 1;
+        lending_market.elevation_groups[1].debt_reserve = debt_reserve;
+
+        let state = KaminoState {
+            reserve_pubkey: Pubkey::new_unique(),
+            reserve,
+            lending_market,
+        };
+        let report = ReserveReport::from(&state);
+        assert_eq!(report.status, "active");
+        assert_eq!(report.borrowed_amount, 1_000);
+        assert!(report.flash_loan_enabled);
+        assert!((report.flash_loan_fee_bps - 10.0).abs() < 0.01);
+        assert_eq!(report.unavailable_reason, None);
+        assert_eq!(
+            report.elevation_groups,
+            vec![ElevationGroupReport {
+                id: 2,
+                ltv_pct: 90,
+                liquidation_threshold_pct: 0,
+                allow_new_loans: true,
+                debt_reserve: debt_reserve.to_string(),
+                borrow_limit_against_collateral: 700,
+            }]
+        );
+
+        let mut state = state;
+        state.reserve.config.fees.flash_loan_fee_sf = u64::MAX;
+        let report = ReserveReport::from(&state);
+        assert!(!report.flash_loan_enabled);
+        assert!(report.unavailable_reason.is_some());
+    }
+}
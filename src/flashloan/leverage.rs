@@ -0,0 +1,135 @@
+use crate::config::LeverageConfig;
+use crate::types::QuoteResponse;
+
+/// 每轮最多将交易数量放大的倍数，避免价格影响估算偏差时数量增长过快
+const MAX_GROWTH: f64 = 2.0;
+
+/// 闪电贷杠杆，交易数量不再固定为 input_amount
+///
+/// 交易数量不超过钱包余额与可借流动性之和，并根据上一轮报价的价格影响调整：
+/// 价格影响低于上限时放大，超出时按比例缩小，因此不需要额外的报价请求
+#[derive(Debug)]
+pub struct Leverage {
+    min_amount: u64,
+    max_amount: u64,
+    max_price_impact: f64,
+    /// 下一轮报价使用的数量
+    target: u64,
+}
+
+impl Leverage {
+    pub fn new(config: &LeverageConfig, input_amount: u64) -> Self {
+        let max_amount = if config.max_amount == 0 {
+            u64::MAX
+        } else {
+            config.max_amount.max(input_amount)
+        };
+        Self {
+            min_amount: input_amount,
+            max_amount,
+            max_price_impact: config.max_price_impact,
+            target: input_amount,
+        }
+    }
+
+    /// 本轮报价使用的数量，最少为 input_amount
+    pub fn amount(&self, balance: u64, available_liquidity: u64) -> u64 {
+        self.target
+            .min(balance.saturating_add(available_liquidity))
+            .max(self.min_amount)
+    }
+
+    pub fn target(&self) -> u64 {
+        self.target
+    }
+
+    /// 根据本轮报价的价格影响调整下一轮的数量，返回本轮是否超出价格影响上限
+    ///
+    /// 交易数量为 input_amount 时不检查价格影响，与未启用杠杆时一致
+    pub fn observe(&mut self, amount: u64, price_impact: f64) -> bool {
+        // 价格影响近似与交易数量成正比
+        let scale = if price_impact <= 0.0 {
+            MAX_GROWTH
+        } else {
+            (self.max_price_impact / price_impact).min(MAX_GROWTH)
+        };
+        self.target = ((amount as f64 * scale) as u64).clamp(self.min_amount, self.max_amount);
+        amount > self.min_amount && price_impact > self.max_price_impact
+    }
+
+    /// 没有来源可借出该数量(超出借款上限、使用率上限等)时，下一轮数量减半
+    pub fn shrink(&mut self, amount: u64) {
+        self.target = (amount / 2).max(self.min_amount);
+    }
+}
+
+/// 两次报价的价格影响之和
+pub fn price_impact(quote1: &QuoteResponse, quote2: &QuoteResponse) -> f64 {
+    [quote1, quote2]
+        .iter()
+        .map(|quote| quote.price_impact_pct.parse::<f64>().unwrap_or(0.0).abs())
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn new_leverage(max_amount: u64) -> Leverage {
+        Leverage::new(
+            &LeverageConfig {
+                enabled: true,
+                max_amount,
+                max_price_impact: 0.01,
+            },
+            1_000,
+        )
+    }
+
+    #[test]
+    fn test_amount() {
+        let mut leverage = new_leverage(0);
+        assert_eq!(leverage.amount(500, 100_000), 1_000);
+
+        // 价格影响为上限的 1/4 时最多放大 2 倍
+        assert!(!leverage.observe(1_000, 0.0025));
+        assert_eq!(leverage.target(), 2_000);
+        // 不超过余额与可借流动性之和
+        assert_eq!(leverage.amount(500, 1_000), 1_500);
+        // 可借流动性不足时不低于 input_amount
+        assert_eq!(leverage.amount(0, 0), 1_000);
+
+        // 超出上限时按比例缩小，本轮放弃
+        assert!(leverage.observe(2_000, 0.02));
+        assert_eq!(leverage.target(), 1_000);
+        // input_amount 不检查价格影响
+        assert!(!leverage.observe(1_000, 0.05));
+        assert_eq!(leverage.target(), 1_000);
+
+        leverage.observe(1_000, 0.0);
+        leverage.shrink(2_000);
+        assert_eq!(leverage.target(), 1_000);
+    }
+
+    #[test]
+    fn test_max_amount() {
+        let mut leverage = new_leverage(3_000);
+        leverage.observe(2_000, 0.0);
+        assert_eq!(leverage.target(), 3_000);
+
+        // max_amount 小于 input_amount 时以 input_amount 为准
+        let mut leverage = new_leverage(10);
+        leverage.observe(1_000, 0.0);
+        assert_eq!(leverage.target(), 1_000);
+    }
+
+    #[test]
+    fn test_price_impact() {
+        let quote = |price_impact_pct: &str| QuoteResponse {
+            price_impact_pct: price_impact_pct.to_string(),
+            ..Default::default()
+        };
+        assert!((price_impact(&quote("0.002"), &quote("-0.001")) - 0.003).abs() < 1e-12);
+        assert_eq!(price_impact(&quote(""), &quote("0.001")), 0.001);
+    }
+}
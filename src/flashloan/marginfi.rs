@@ -0,0 +1,619 @@
+use super::{FlashLoan, FlashLoanError, SYSVAR};
+use anyhow::{Result, anyhow};
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_program::pubkey::{Pubkey, pubkey};
+use solana_sdk::{
+    hash::hashv,
+    instruction::{AccountMeta, Instruction},
+    signature::{Keypair, Signature},
+    signer::Signer,
+    transaction::Transaction,
+};
+use solana_sdk_ids::system_program;
+use spl_associated_token_account::get_associated_token_address_with_program_id;
+use std::sync::{Arc, RwLock};
+use tokio::time::Duration;
+use tracing::warn;
+
+pub const MARGINFI_PROGRAM_ID: Pubkey = pubkey!("MFv2hWf31Z9kbCa1snEPYctwafyhdvnV7FK1g4XNnYs");
+/// MarginFi 主 group
+pub const MARGINFI_GROUP: Pubkey = pubkey!("4qp6Fx6tnZkY5Wropq9wUYgtFxXKwE6viZxFHg3rdAG8");
+const LIQUIDITY_VAULT: &[u8] = b"liquidity_vault";
+const LIQUIDITY_VAULT_AUTH: &[u8] = b"liquidity_vault_auth";
+/// BankOperationalState::Operational，其余状态(Paused、ReduceOnly 等)不可借款
+const BANK_OPERATIONAL: u8 = 1;
+
+// == MarginFi
+//
+// 交易内的指令布局:
+//   start_flashloan(end_index) + borrow + ... + repay(repay_all) + end_flashloan
+//
+// start_flashloan 需要记录 end_flashloan 的位置，程序通过 Instructions Sysvar 校验；
+// 闪电贷期间跳过健康检查，end_flashloan 时统一检查。marginfi 账户应只用于闪电贷，
+// 不持有其它仓位，这样还款(repay_all)后 end_flashloan 无需附带 bank 及预言机账户
+#[derive(Clone)]
+pub struct MarginFi {
+    pub user: Pubkey,
+    pub liquidity_amount: u64,
+    pub marginfi_account: Pubkey,
+    pub bank_pubkey: Pubkey,
+    pub bank: Bank,
+    /// 借款 mint 所属的 token 程序
+    pub token_program: Pubkey,
+    /// 加载时 bank 流动性金库的余额
+    pub vault_balance: u64,
+}
+
+/// Bank 账户中用到的字段
+///
+/// https://github.com/mrgnlabs/marginfi-v2/blob/main/programs/marginfi/src/state/marginfi_group.rs
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Bank {
+    pub mint: Pubkey,
+    pub group: Pubkey,
+    /// 总借款数量，total_liability_shares * liability_share_value
+    pub total_liabilities: u64,
+    /// config.operational_state: 0 Paused, 1 Operational, 2 ReduceOnly
+    pub operational_state: u8,
+    /// config.borrow_limit，u64::MAX 表示不限制
+    pub borrow_limit: u64,
+}
+
+impl Bank {
+    /// 借款前检查 bank 状态、金库余额及借款上限
+    pub fn check_borrow(&self, amount: u64, vault_balance: u64) -> Result<(), FlashLoanError> {
+        if self.operational_state != BANK_OPERATIONAL {
+            return Err(FlashLoanError::BankNotOperational(self.operational_state));
+        }
+
+        if amount > vault_balance {
+            return Err(FlashLoanError::InsufficientLiquidity {
+                available: vault_balance,
+                amount,
+            });
+        }
+
+        if self.borrow_limit != u64::MAX {
+            let borrowed_after = self.total_liabilities.saturating_add(amount);
+            if borrowed_after > self.borrow_limit {
+                return Err(FlashLoanError::BorrowLimitExceeded {
+                    borrowed: borrowed_after,
+                    limit: self.borrow_limit,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// 能通过 check_borrow 的最大借款数量，bank 不可借款时为 0
+    pub fn max_borrow(&self, vault_balance: u64) -> u64 {
+        if self.operational_state != BANK_OPERATIONAL {
+            return 0;
+        }
+        if self.borrow_limit == u64::MAX {
+            return vault_balance;
+        }
+        vault_balance.min(self.borrow_limit.saturating_sub(self.total_liabilities))
+    }
+}
+
+impl MarginFi {
+    pub async fn new(
+        rpc_client: Arc<RpcClient>,
+        user: Pubkey,
+        liquidity_amount: u64,
+        bank_pubkey: Pubkey,
+        marginfi_account: Pubkey,
+        mint: Pubkey,
+    ) -> Result<MarginFi> {
+        let bank = fetch_bank(&rpc_client, &bank_pubkey).await?;
+        if bank.mint != mint {
+            return Err(anyhow!(
+                "MarginFi bank {} 的 mint 为 {}，与借款 mint {} 不一致",
+                bank_pubkey,
+                bank.mint,
+                mint
+            ));
+        }
+
+        let account_data = rpc_client
+            .get_account_data(&marginfi_account)
+            .await
+            .map_err(|e| anyhow!("获取 marginfi 账户 {} 失败: {}", marginfi_account, e))?;
+        let (group, authority) = parse_marginfi_account(&account_data)?;
+        if authority != user {
+            return Err(anyhow!(
+                "marginfi 账户 {} 的 authority 为 {}，与当前钱包 {} 不一致",
+                marginfi_account,
+                authority,
+                user
+            ));
+        }
+        if group != bank.group {
+            return Err(anyhow!(
+                "marginfi 账户 {} 与 bank {} 不属于同一个 group",
+                marginfi_account,
+                bank_pubkey
+            ));
+        }
+
+        let token_program = rpc_client
+            .get_account(&mint)
+            .await
+            .map_err(|e| anyhow!("获取 mint {} 失败: {}", mint, e))?
+            .owner;
+
+        let mut marginfi = MarginFi {
+            user,
+            liquidity_amount,
+            marginfi_account,
+            bank_pubkey,
+            bank,
+            token_program,
+            vault_balance: 0,
+        };
+        marginfi.vault_balance = fetch_vault_balance(&rpc_client, &marginfi).await?;
+        Ok(marginfi)
+    }
+
+    fn liquidity_vault(&self) -> Pubkey {
+        Pubkey::find_program_address(
+            &[LIQUIDITY_VAULT, self.bank_pubkey.as_ref()],
+            &MARGINFI_PROGRAM_ID,
+        )
+        .0
+    }
+
+    fn liquidity_vault_authority(&self) -> Pubkey {
+        Pubkey::find_program_address(
+            &[LIQUIDITY_VAULT_AUTH, self.bank_pubkey.as_ref()],
+            &MARGINFI_PROGRAM_ID,
+        )
+        .0
+    }
+
+    fn user_ata(&self) -> Pubkey {
+        get_associated_token_address_with_program_id(
+            &self.user,
+            &self.bank.mint,
+            &self.token_program,
+        )
+    }
+
+    /// Token-2022 的转账需要附带 mint 账户
+    fn transfer_remaining_accounts(&self) -> Vec<AccountMeta> {
+        if self.token_program == spl_token::ID {
+            vec![]
+        } else {
+            vec![AccountMeta::new_readonly(self.bank.mint, false)]
+        }
+    }
+}
+
+/// 长期持有的 MarginFi bank，只在启动时校验一次账户，之后由后台任务定期刷新 bank 状态及金库余额
+pub struct MarginFiBank {
+    template: MarginFi,
+    /// 最近一次读取的 bank 及金库余额
+    state: RwLock<(Bank, u64)>,
+}
+
+impl MarginFiBank {
+    pub async fn start(
+        rpc_client: Arc<RpcClient>,
+        user: Pubkey,
+        bank_pubkey: Pubkey,
+        marginfi_account: Pubkey,
+        mint: Pubkey,
+        interval: Duration,
+    ) -> Result<Arc<Self>> {
+        let template = MarginFi::new(
+            rpc_client.clone(),
+            user,
+            0,
+            bank_pubkey,
+            marginfi_account,
+            mint,
+        )
+        .await?;
+        let handle = Arc::new(Self::new(template));
+
+        let refresher = Arc::downgrade(&handle);
+        tokio::spawn(async move {
+            loop {
+                tokio::time::sleep(interval).await;
+                // 所有持有者都已释放时停止刷新
+                let Some(handle) = refresher.upgrade() else {
+                    break;
+                };
+                match fetch_state(&rpc_client, &handle.template).await {
+                    Ok(state) => *handle.state.write().unwrap() = state,
+                    Err(e) => warn!("{}", e),
+                }
+            }
+        });
+
+        Ok(handle)
+    }
+
+    pub(super) fn new(template: MarginFi) -> Self {
+        Self {
+            state: RwLock::new((template.bank, template.vault_balance)),
+            template,
+        }
+    }
+
+    /// 使用当前 bank 状态及金库余额生成一次借款
+    pub fn flash_loan(&self, liquidity_amount: u64) -> MarginFi {
+        let (bank, vault_balance) = *self.state.read().unwrap();
+        MarginFi {
+            liquidity_amount,
+            bank,
+            vault_balance,
+            ..self.template.clone()
+        }
+    }
+}
+
+async fn fetch_bank(rpc_client: &RpcClient, bank_pubkey: &Pubkey) -> Result<Bank> {
+    let bank_data = rpc_client
+        .get_account_data(bank_pubkey)
+        .await
+        .map_err(|e| anyhow!("获取 MarginFi bank {} 失败: {}", bank_pubkey, e))?;
+    parse_bank(&bank_data)
+}
+
+async fn fetch_state(rpc_client: &RpcClient, marginfi: &MarginFi) -> Result<(Bank, u64)> {
+    let bank = fetch_bank(rpc_client, &marginfi.bank_pubkey).await?;
+    let vault_balance = fetch_vault_balance(rpc_client, marginfi).await?;
+    Ok((bank, vault_balance))
+}
+
+async fn fetch_vault_balance(rpc_client: &RpcClient, marginfi: &MarginFi) -> Result<u64> {
+    let balance = rpc_client
+        .get_token_account_balance(&marginfi.liquidity_vault())
+        .await
+        .map_err(|e| {
+            anyhow!(
+                "获取 MarginFi bank {} 流动性失败: {}",
+                marginfi.bank_pubkey,
+                e
+            )
+        })?;
+    Ok(balance.amount.parse()?)
+}
+
+impl FlashLoan for MarginFi {
+    fn borrow(&self, _borrow_index: u8, end_index: u8) -> Vec<Instruction> {
+        let mut start_data = discriminator("lending_account_start_flashloan").to_vec();
+        start_data.extend((end_index as u64).to_le_bytes());
+        let start = Instruction {
+            program_id: MARGINFI_PROGRAM_ID,
+            accounts: vec![
+                AccountMeta::new(self.marginfi_account, false),
+                AccountMeta::new_readonly(self.user, true),
+                AccountMeta::new_readonly(SYSVAR, false),
+            ],
+            data: start_data,
+        };
+
+        let mut borrow_data = discriminator("lending_account_borrow").to_vec();
+        borrow_data.extend(self.liquidity_amount.to_le_bytes());
+        let mut accounts = vec![
+            AccountMeta::new_readonly(self.bank.group, false),
+            AccountMeta::new(self.marginfi_account, false),
+            AccountMeta::new_readonly(self.user, true),
+            AccountMeta::new(self.bank_pubkey, false),
+            AccountMeta::new(self.user_ata(), false),
+            AccountMeta::new(self.liquidity_vault_authority(), false),
+            AccountMeta::new(self.liquidity_vault(), false),
+            AccountMeta::new_readonly(self.token_program, false),
+        ];
+        accounts.extend(self.transfer_remaining_accounts());
+        let borrow = Instruction {
+            program_id: MARGINFI_PROGRAM_ID,
+            accounts,
+            data: borrow_data,
+        };
+
+        vec![start, borrow]
+    }
+
+    fn repay(&self, _borrow_index: u8) -> Vec<Instruction> {
+        // amount + repay_all: Option<bool> = Some(true)，归还全部借款并关闭该仓位
+        let mut repay_data = discriminator("lending_account_repay").to_vec();
+        repay_data.extend(self.liquidity_amount.to_le_bytes());
+        repay_data.extend([1, 1]);
+        let mut accounts = vec![
+            AccountMeta::new_readonly(self.bank.group, false),
+            AccountMeta::new(self.marginfi_account, false),
+            AccountMeta::new_readonly(self.user, true),
+            AccountMeta::new(self.bank_pubkey, false),
+            AccountMeta::new(self.user_ata(), false),
+            AccountMeta::new(self.liquidity_vault(), false),
+            AccountMeta::new_readonly(self.token_program, false),
+        ];
+        accounts.extend(self.transfer_remaining_accounts());
+        let repay = Instruction {
+            program_id: MARGINFI_PROGRAM_ID,
+            accounts,
+            data: repay_data,
+        };
+
+        let end = Instruction {
+            program_id: MARGINFI_PROGRAM_ID,
+            accounts: vec![
+                AccountMeta::new(self.marginfi_account, false),
+                AccountMeta::new_readonly(self.user, true),
+            ],
+            data: discriminator("lending_account_end_flashloan").to_vec(),
+        };
+
+        vec![repay, end]
+    }
+
+    fn instruction_count(&self) -> (usize, usize) {
+        (2, 2)
+    }
+
+    fn available_liquidity(&self) -> u64 {
+        self.bank.max_borrow(self.vault_balance)
+    }
+
+    fn check(&self) -> Result<(), FlashLoanError> {
+        self.bank
+            .check_borrow(self.liquidity_amount, self.vault_balance)
+    }
+
+    /// MarginFi 闪电贷不收取费用
+    fn fee(&self) -> u64 {
+        0
+    }
+}
+
+/// Anchor 指令 discriminator: sha256("global:<name>")[..8]
+fn discriminator(name: &str) -> [u8; 8] {
+    let hash = hashv(&[b"global:", name.as_bytes()]);
+    hash.to_bytes()[..8].try_into().unwrap()
+}
+
+/// Bank(zero_copy) 中用到的字段的偏移，包含 8 字节 discriminator:
+///
+/// mint(8) mint_decimals(40) group(41) _pad0(73) asset_share_value(80) liability_share_value(96)
+/// ... total_liability_shares(256) total_asset_shares(272) last_update(288) config(296)
+///
+/// BankConfig: 4 个权重(64) deposit_limit(8) interest_rate_config(240) operational_state(1)
+/// oracle_setup(1) oracle_keys(160) _pad0(6) borrow_limit(8) ...
+const BANK_LIABILITY_SHARE_VALUE: usize = 96;
+const BANK_TOTAL_LIABILITY_SHARES: usize = 256;
+const BANK_OPERATIONAL_STATE: usize = 608;
+const BANK_BORROW_LIMIT: usize = 776;
+
+fn parse_bank(data: &[u8]) -> Result<Bank> {
+    if data.len() < BANK_BORROW_LIMIT + 8 {
+        return Err(anyhow!("MarginFi bank 数据长度 {} 不正确", data.len()));
+    }
+    let liability_share_value = i80f48(&data[BANK_LIABILITY_SHARE_VALUE..]);
+    let total_liability_shares = i80f48(&data[BANK_TOTAL_LIABILITY_SHARES..]);
+    Ok(Bank {
+        mint: Pubkey::try_from(&data[8..40]).unwrap(),
+        group: Pubkey::try_from(&data[41..73]).unwrap(),
+        total_liabilities: (total_liability_shares * liability_share_value).ceil() as u64,
+        operational_state: data[BANK_OPERATIONAL_STATE],
+        borrow_limit: u64::from_le_bytes(
+            data[BANK_BORROW_LIMIT..BANK_BORROW_LIMIT + 8]
+                .try_into()
+                .unwrap(),
+        ),
+    })
+}
+
+/// WrappedI80F48: 小端 i128，低 48 位为小数部分
+fn i80f48(data: &[u8]) -> f64 {
+    let raw = i128::from_le_bytes(data[..16].try_into().unwrap());
+    raw as f64 / (1u64 << 48) as f64
+}
+
+/// MarginfiAccount: discriminator(8) + group(32) + authority(32) + ...，返回 (group, authority)
+fn parse_marginfi_account(data: &[u8]) -> Result<(Pubkey, Pubkey)> {
+    if data.len() < 72 {
+        return Err(anyhow!("marginfi 账户数据长度 {} 不正确", data.len()));
+    }
+    Ok((
+        Pubkey::try_from(&data[8..40]).unwrap(),
+        Pubkey::try_from(&data[40..72]).unwrap(),
+    ))
+}
+
+/// 在 group 下创建 marginfi 账户，authority 为 payer
+pub async fn create_marginfi_account(
+    rpc_client: &RpcClient,
+    payer: &Keypair,
+    group: &Pubkey,
+) -> Result<(Pubkey, Signature)> {
+    let account = Keypair::new();
+    let ix = Instruction {
+        program_id: MARGINFI_PROGRAM_ID,
+        accounts: vec![
+            AccountMeta::new_readonly(*group, false),
+            AccountMeta::new(account.pubkey(), true),
+            AccountMeta::new_readonly(payer.pubkey(), true),
+            AccountMeta::new(payer.pubkey(), true),
+            AccountMeta::new_readonly(system_program::ID, false),
+        ],
+        data: discriminator("marginfi_account_initialize").to_vec(),
+    };
+
+    let recent_blockhash = rpc_client.get_latest_blockhash().await?;
+    let tx = Transaction::new_signed_with_payer(
+        &[ix],
+        Some(&payer.pubkey()),
+        &[payer, &account],
+        recent_blockhash,
+    );
+    let signature = rpc_client
+        .send_and_confirm_transaction(&tx)
+        .await
+        .map_err(|e| anyhow!("{}", e))?;
+    Ok((account.pubkey(), signature))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bank() -> Bank {
+        Bank {
+            mint: Pubkey::new_unique(),
+            group: MARGINFI_GROUP,
+            total_liabilities: 0,
+            operational_state: BANK_OPERATIONAL,
+            borrow_limit: u64::MAX,
+        }
+    }
+
+    fn marginfi() -> MarginFi {
+        MarginFi {
+            user: Pubkey::new_unique(),
+            liquidity_amount: 1_000_000,
+            marginfi_account: Pubkey::new_unique(),
+            bank_pubkey: Pubkey::new_unique(),
+            bank: bank(),
+            token_program: spl_token::ID,
+            vault_balance: 0,
+        }
+    }
+
+    #[test]
+    fn test_flashloan_instructions() {
+        let marginfi = marginfi();
+        let borrow = marginfi.borrow(3, 9);
+        assert_eq!(borrow.len(), 2);
+        // start_flashloan 记录 end_flashloan 的位置
+        assert_eq!(
+            borrow[0].data[..8],
+            discriminator("lending_account_start_flashloan")
+        );
+        assert_eq!(borrow[0].data[8..], 9u64.to_le_bytes());
+        assert_eq!(borrow[1].data[8..], 1_000_000u64.to_le_bytes());
+        assert_eq!(borrow[1].accounts[4].pubkey, marginfi.user_ata());
+
+        let repay = marginfi.repay(3);
+        assert_eq!(repay.len(), 2);
+        assert_eq!(repay[0].data[8..16], 1_000_000u64.to_le_bytes());
+        assert_eq!(repay[0].data[16..], [1, 1]);
+        assert_eq!(
+            repay[1].data,
+            discriminator("lending_account_end_flashloan")
+        );
+        assert_eq!(marginfi.instruction_count(), (borrow.len(), repay.len()));
+
+        // Token-2022 附带 mint 账户
+        let marginfi = MarginFi {
+            token_program: Pubkey::new_unique(),
+            ..marginfi
+        };
+        let borrow = marginfi.borrow(3, 9);
+        assert_eq!(
+            borrow[1].accounts.last().unwrap().pubkey,
+            marginfi.bank.mint
+        );
+    }
+
+    #[test]
+    fn test_check_borrow() {
+        let bank = bank();
+        assert!(bank.check_borrow(1_000, 1_000).is_ok());
+        assert!(matches!(
+            bank.check_borrow(1_001, 1_000),
+            Err(FlashLoanError::InsufficientLiquidity {
+                available: 1_000,
+                amount: 1_001
+            })
+        ));
+
+        // 暂停或只允许还款时不可借款
+        for state in [0, 2] {
+            let bank = Bank {
+                operational_state: state,
+                ..bank
+            };
+            assert!(matches!(
+                bank.check_borrow(1_000, 1_000),
+                Err(FlashLoanError::BankNotOperational(s)) if s == state
+            ));
+        }
+
+        let bank = Bank {
+            total_liabilities: 9_500,
+            borrow_limit: 10_000,
+            ..bank
+        };
+        assert!(bank.check_borrow(500, 1_000).is_ok());
+        assert!(matches!(
+            bank.check_borrow(501, 1_000),
+            Err(FlashLoanError::BorrowLimitExceeded {
+                borrowed: 10_001,
+                limit: 10_000
+            })
+        ));
+    }
+
+    #[test]
+    fn test_max_borrow() {
+        let bank = bank();
+        assert_eq!(bank.max_borrow(1_000), 1_000);
+
+        // 借款上限比金库余额更紧
+        let bank = Bank {
+            total_liabilities: 9_500,
+            borrow_limit: 10_000,
+            ..bank
+        };
+        assert_eq!(bank.max_borrow(1_000), 500);
+        assert!(bank.check_borrow(500, 1_000).is_ok());
+        assert!(bank.check_borrow(501, 1_000).is_err());
+        assert_eq!(bank.max_borrow(300), 300);
+
+        let bank = Bank {
+            operational_state: 0,
+            ..bank
+        };
+        assert_eq!(bank.max_borrow(1_000), 0);
+    }
+
+    #[test]
+    fn test_parse_accounts() {
+        let mint = Pubkey::new_unique();
+        let mut data = vec![0u8; 1_000];
+        data[8..40].copy_from_slice(mint.as_ref());
+        data[40] = 9;
+        data[41..73].copy_from_slice(MARGINFI_GROUP.as_ref());
+        // liability_share_value = 1.5，total_liability_shares = 2000
+        data[BANK_LIABILITY_SHARE_VALUE..BANK_LIABILITY_SHARE_VALUE + 16]
+            .copy_from_slice(&((3i128 << 48) / 2).to_le_bytes());
+        data[BANK_TOTAL_LIABILITY_SHARES..BANK_TOTAL_LIABILITY_SHARES + 16]
+            .copy_from_slice(&(2_000i128 << 48).to_le_bytes());
+        data[BANK_OPERATIONAL_STATE] = BANK_OPERATIONAL;
+        data[BANK_BORROW_LIMIT..BANK_BORROW_LIMIT + 8].copy_from_slice(&5_000u64.to_le_bytes());
+        assert_eq!(
+            parse_bank(&data).unwrap(),
+            Bank {
+                mint,
+                group: MARGINFI_GROUP,
+                total_liabilities: 3_000,
+                operational_state: BANK_OPERATIONAL,
+                borrow_limit: 5_000,
+            }
+        );
+        assert!(parse_bank(&data[..BANK_BORROW_LIMIT]).is_err());
+
+        let authority = Pubkey::new_unique();
+        let mut data = vec![0u8; 200];
+        data[8..40].copy_from_slice(MARGINFI_GROUP.as_ref());
+        data[40..72].copy_from_slice(authority.as_ref());
+        assert_eq!(
+            parse_marginfi_account(&data).unwrap(),
+            (MARGINFI_GROUP, authority)
+        );
+    }
+}
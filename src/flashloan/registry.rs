@@ -0,0 +1,394 @@
+use super::marginfi::MarginFiBank;
+use super::{FlashLoan, KaminoReserve, referrer_token_state};
+use crate::config::{self, FLASH_LOAN_KAMINO, FLASH_LOAN_MARGINFI};
+use anyhow::{Result, anyhow};
+use futures_util::future::join_all;
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_program::pubkey::Pubkey;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::Arc;
+use tokio::time::Duration;
+use tracing::{debug, error, warn};
+
+/// 解析后的闪电贷来源
+#[derive(Debug, Clone, PartialEq)]
+pub struct Source {
+    pub provider: String,
+    /// kamino 为 reserve 地址，marginfi 为 bank 地址
+    pub reserve: Pubkey,
+    pub marginfi_account: Option<Pubkey>,
+}
+
+impl fmt::Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.provider, self.reserve)
+    }
+}
+
+impl Source {
+    fn parse(config: &config::FlashLoanSource) -> Result<Self> {
+        let reserve = Pubkey::from_str(&config.reserve)
+            .map_err(|e| anyhow!("无效的闪电贷 reserve {}: {}", config.reserve, e))?;
+        let marginfi_account = match config.provider.as_str() {
+            FLASH_LOAN_KAMINO => None,
+            FLASH_LOAN_MARGINFI => {
+                Some(Pubkey::from_str(&config.marginfi_account).map_err(|e| {
+                    anyhow!("无效的 marginfi 账户 {}: {}", config.marginfi_account, e)
+                })?)
+            }
+            provider => return Err(anyhow!("不支持的闪电贷平台: {}", provider)),
+        };
+        Ok(Self {
+            provider: config.provider.clone(),
+            reserve,
+            marginfi_account,
+        })
+    }
+
+    async fn start(
+        &self,
+        rpc_client: Arc<RpcClient>,
+        user: Pubkey,
+        mint: Pubkey,
+        interval: Duration,
+        referrer: Option<Pubkey>,
+    ) -> Result<Handle> {
+        Ok(match self.marginfi_account {
+            Some(marginfi_account) => Handle::MarginFi(
+                MarginFiBank::start(
+                    rpc_client,
+                    user,
+                    self.reserve,
+                    marginfi_account,
+                    mint,
+                    interval,
+                )
+                .await?,
+            ),
+            None => {
+                // 推荐人 token state 不存在时带推荐人的借款指令将失败，该 reserve 不使用推荐人
+                let referrer = match referrer {
+                    Some(referrer) => {
+                        let token_state = referrer_token_state(&referrer, &self.reserve);
+                        let response = rpc_client
+                            .get_account_with_commitment(&token_state, rpc_client.commitment())
+                            .await
+                            .map_err(|e| {
+                                anyhow!("获取推荐人 token state {} 失败: {}", token_state, e)
+                            })?;
+                        if response.value.is_none() {
+                            warn!(
+                                "推荐人 {} 未在 reserve {} 上初始化 token state {}，该来源不使用推荐人",
+                                referrer, self.reserve, token_state
+                            );
+                        }
+                        response.value.map(|_| referrer)
+                    }
+                    None => None,
+                };
+                let reserve = KaminoReserve::start(rpc_client, self.reserve, interval).await?;
+                reserve.state().check_mint(&mint)?;
+                Handle::Kamino(reserve, referrer)
+            }
+        })
+    }
+}
+
+/// 已加载的闪电贷来源，状态由后台任务刷新
+#[derive(Clone)]
+enum Handle {
+    /// reserve 及推荐人
+    Kamino(Arc<KaminoReserve>, Option<Pubkey>),
+    MarginFi(Arc<MarginFiBank>),
+}
+
+impl Handle {
+    fn flash_loan(&self, user: Pubkey, amount: u64) -> Box<dyn FlashLoan> {
+        match self {
+            Handle::Kamino(reserve, referrer) => {
+                Box::new(reserve.flash_loan(user, amount, *referrer))
+            }
+            Handle::MarginFi(bank) => Box::new(bank.flash_loan(amount)),
+        }
+    }
+}
+
+/// 选中的闪电贷来源
+pub struct Selected {
+    pub flashloan: Box<dyn FlashLoan>,
+    pub source: Source,
+    /// 借款数量
+    pub amount: u64,
+    /// 借款费用，单位为借款 mint 的最小单位
+    pub fee: u64,
+}
+
+impl fmt::Debug for Selected {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Selected")
+            .field("source", &self.source)
+            .field("amount", &self.amount)
+            .field("fee", &self.fee)
+            .finish()
+    }
+}
+
+/// 已配置的全部闪电贷来源
+///
+/// 启动时加载一次全部来源，之后每次借款只读取缓存的状态，不请求 RPC
+#[derive(Clone)]
+pub struct FlashLoanRegistry {
+    user: Pubkey,
+    sources: Vec<(Source, Handle)>,
+}
+
+impl FlashLoanRegistry {
+    /// 加载失败或 mint 与借款 mint 不一致的来源将被忽略，没有可用来源时返回错误
+    pub async fn start(
+        config: &config::FlashLoan,
+        rpc_client: Arc<RpcClient>,
+        user: Pubkey,
+        mint: Pubkey,
+    ) -> Result<Self> {
+        let interval = config.refresh_interval_to_duration();
+        let parsed = parse_sources(config)?;
+        let referrer = parse_referrer(config)?;
+        let handles = join_all(
+            parsed
+                .iter()
+                .map(|source| source.start(rpc_client.clone(), user, mint, interval, referrer)),
+        )
+        .await;
+
+        let mut sources = Vec::with_capacity(parsed.len());
+        for (source, handle) in parsed.into_iter().zip(handles) {
+            match handle {
+                Ok(handle) => sources.push((source, handle)),
+                Err(e) => error!("加载闪电贷来源 {} 失败，已忽略: {}", source, e),
+            }
+        }
+        if sources.is_empty() {
+            return Err(anyhow!("没有可用的闪电贷来源"));
+        }
+        Ok(Self { user, sources })
+    }
+
+    pub fn sources(&self) -> impl Iterator<Item = &Source> {
+        self.sources.iter().map(|(source, _)| source)
+    }
+
+    /// 各来源中最多可借出的数量
+    ///
+    /// 每个来源取能通过借款前检查的最大数量(已考虑借款上限、使用率上限及暂停状态)，
+    /// 按该数量借款时 select 至少能选中一个来源
+    pub fn available_liquidity(&self) -> u64 {
+        self.sources
+            .iter()
+            .map(|(_, handle)| handle.flash_loan(self.user, 0).available_liquidity())
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// 返回流动性充足且费用最低的来源
+    ///
+    /// 借款前检查未通过(暂停借款、流动性不足、超出借款上限等)的来源将被跳过，
+    /// 费用相同时按配置顺序选择
+    pub fn select(&self, amount: u64) -> Result<Selected> {
+        let mut candidates = Vec::with_capacity(self.sources.len());
+        let mut rejected = Vec::new();
+        for (source, handle) in &self.sources {
+            let flashloan = handle.flash_loan(self.user, amount);
+            if let Err(e) = flashloan.check() {
+                debug!("跳过闪电贷来源 {}: {}", source, e);
+                rejected.push(format!("{}: {}", source, e));
+                continue;
+            }
+            candidates.push(Selected {
+                fee: flashloan.fee(),
+                flashloan,
+                source: source.clone(),
+                amount,
+            });
+        }
+
+        pick_cheapest(candidates).ok_or_else(|| {
+            anyhow!(
+                "没有可借出 {} 的闪电贷来源 ({})",
+                amount,
+                rejected.join("; ")
+            )
+        })
+    }
+}
+
+fn parse_sources(config: &config::FlashLoan) -> Result<Vec<Source>> {
+    let sources = config
+        .all_sources()
+        .map(Source::parse)
+        .collect::<Result<Vec<_>>>()?;
+    if sources.is_empty() {
+        return Err(anyhow!("未配置闪电贷来源"));
+    }
+    Ok(sources)
+}
+
+fn parse_referrer(config: &config::FlashLoan) -> Result<Option<Pubkey>> {
+    if config.kamino_referrer.is_empty() {
+        return Ok(None);
+    }
+    Pubkey::from_str(&config.kamino_referrer)
+        .map(Some)
+        .map_err(|e| anyhow!("无效的 Kamino 推荐人 {}: {}", config.kamino_referrer, e))
+}
+
+/// 费用最低的来源，费用相同时取靠前的来源
+fn pick_cheapest(candidates: Vec<Selected>) -> Option<Selected> {
+    candidates.into_iter().min_by_key(|selected| selected.fee)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::FlashLoanSource;
+    use crate::flashloan::marginfi::{Bank, MARGINFI_GROUP, MarginFi};
+    use crate::flashloan::{FlashLoanError, NoFlashLoan};
+
+    fn source(provider: &str) -> FlashLoanSource {
+        FlashLoanSource {
+            provider: provider.to_string(),
+            reserve: Pubkey::new_unique().to_string(),
+            marginfi_account: Pubkey::new_unique().to_string(),
+        }
+    }
+
+    #[test]
+    fn test_parse_sources() {
+        let config = config::FlashLoan {
+            source: source(FLASH_LOAN_KAMINO),
+            sources: vec![source(FLASH_LOAN_MARGINFI), source(FLASH_LOAN_KAMINO)],
+            ..Default::default()
+        };
+        let sources = parse_sources(&config).unwrap();
+        assert_eq!(sources.len(), 3);
+        assert!(sources[0].marginfi_account.is_none());
+        assert!(sources[1].marginfi_account.is_some());
+
+        // reserve 为空的来源忽略
+        let config = config::FlashLoan {
+            sources: vec![source(FLASH_LOAN_KAMINO)],
+            ..Default::default()
+        };
+        assert_eq!(parse_sources(&config).unwrap().len(), 1);
+        assert!(parse_sources(&config::FlashLoan::default()).is_err());
+
+        let config = config::FlashLoan {
+            source: source("solend"),
+            ..Default::default()
+        };
+        assert!(parse_sources(&config).is_err());
+    }
+
+    #[test]
+    fn test_parse_referrer() {
+        assert_eq!(parse_referrer(&config::FlashLoan::default()).unwrap(), None);
+
+        let referrer = Pubkey::new_unique();
+        let config = config::FlashLoan {
+            kamino_referrer: referrer.to_string(),
+            ..Default::default()
+        };
+        assert_eq!(parse_referrer(&config).unwrap(), Some(referrer));
+
+        let config = config::FlashLoan {
+            kamino_referrer: "invalid".to_string(),
+            ..Default::default()
+        };
+        assert!(parse_referrer(&config).is_err());
+    }
+
+    #[test]
+    fn test_pick_cheapest() {
+        let selected = |fee: u64| Selected {
+            flashloan: Box::new(NoFlashLoan),
+            source: Source::parse(&source(FLASH_LOAN_KAMINO)).unwrap(),
+            amount: 0,
+            fee,
+        };
+        let candidates = vec![selected(500), selected(0), selected(300), selected(0)];
+        let reserve = candidates[1].source.reserve;
+        let cheapest = pick_cheapest(candidates).unwrap();
+        assert_eq!(cheapest.fee, 0);
+        // 费用相同时取靠前的来源
+        assert_eq!(cheapest.source.reserve, reserve);
+        assert!(pick_cheapest(vec![]).is_none());
+    }
+
+    #[test]
+    fn test_select_fallback() {
+        let bank = |operational_state: u8, total_liabilities: u64, vault_balance: u64| {
+            let template = MarginFi {
+                user: Pubkey::new_unique(),
+                liquidity_amount: 0,
+                marginfi_account: Pubkey::new_unique(),
+                bank_pubkey: Pubkey::new_unique(),
+                bank: Bank {
+                    mint: Pubkey::new_unique(),
+                    group: MARGINFI_GROUP,
+                    total_liabilities,
+                    operational_state,
+                    borrow_limit: 10_000,
+                },
+                token_program: spl_token::ID,
+                vault_balance,
+            };
+            let source = Source {
+                provider: FLASH_LOAN_MARGINFI.to_string(),
+                reserve: template.bank_pubkey,
+                marginfi_account: Some(template.marginfi_account),
+            };
+            (
+                source,
+                Handle::MarginFi(Arc::new(MarginFiBank::new(template))),
+            )
+        };
+        let registry = FlashLoanRegistry {
+            user: Pubkey::new_unique(),
+            sources: vec![
+                // 已暂停
+                bank(0, 0, 5_000),
+                // 借款后超过借款上限
+                bank(1, 8_000, 5_000),
+                bank(1, 0, 5_000),
+            ],
+        };
+
+        // 已暂停及超过借款上限的来源不计入可借数量
+        assert_eq!(registry.available_liquidity(), 5_000);
+        let registry_limited = FlashLoanRegistry {
+            user: registry.user,
+            sources: registry.sources[..2].to_vec(),
+        };
+        assert_eq!(registry_limited.available_liquidity(), 2_000);
+        assert!(registry_limited.select(2_000).is_ok());
+        assert!(registry_limited.select(2_001).is_err());
+
+        // 跳过未通过检查的来源，选择后面可用的来源
+        let selected = registry.select(3_000).unwrap();
+        assert_eq!(selected.source, registry.sources[2].0);
+        assert!(selected.flashloan.check().is_ok());
+
+        // 全部来源都不可用时返回错误，并列出每个来源的原因
+        let err = registry.select(6_000).unwrap_err().to_string();
+        assert!(err.contains(&FlashLoanError::BankNotOperational(0).to_string()));
+        assert!(
+            err.contains(
+                &FlashLoanError::InsufficientLiquidity {
+                    available: 5_000,
+                    amount: 6_000
+                }
+                .to_string()
+            )
+        );
+    }
+}
@@ -0,0 +1,415 @@
+use crate::config::{JitoConfig, RegionMode};
+use crate::util;
+use anyhow::{Result, anyhow};
+use jito_sdk_rust::{JitoJsonRpcSDK, http_client::IpSelectAlgorithm as JitoAlgorithm};
+use serde_json::Value;
+use std::net::IpAddr;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use tokio::sync::mpsc;
+use tokio::time::{Duration, Instant};
+use tracing::{debug, info, warn};
+
+/// Jito 全局限流时返回的错误码
+const RATE_LIMITED_CODE: i64 = -32097;
+/// 未探测或探测失败时的延时
+const UNKNOWN_LATENCY: u64 = u64::MAX;
+const MIN_PROBE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// 单个区域的统计信息
+#[derive(Debug)]
+pub struct RegionStats {
+    sent: AtomicU64,
+    success: AtomicU64,
+    failed: AtomicU64,
+    rate_limited: AtomicU64,
+    /// 发送延时的指数移动平均值(ms)
+    send_latency_ms: AtomicU64,
+    /// 最近一次探测延时(ms)
+    probe_latency_ms: AtomicU64,
+}
+
+impl Default for RegionStats {
+    fn default() -> Self {
+        Self {
+            sent: AtomicU64::new(0),
+            success: AtomicU64::new(0),
+            failed: AtomicU64::new(0),
+            rate_limited: AtomicU64::new(0),
+            send_latency_ms: AtomicU64::new(UNKNOWN_LATENCY),
+            probe_latency_ms: AtomicU64::new(UNKNOWN_LATENCY),
+        }
+    }
+}
+
+impl RegionStats {
+    fn record_send(&self, latency: Duration, outcome: SendOutcome) {
+        self.sent.fetch_add(1, Ordering::Relaxed);
+        match outcome {
+            SendOutcome::Success => self.success.fetch_add(1, Ordering::Relaxed),
+            SendOutcome::RateLimited => self.rate_limited.fetch_add(1, Ordering::Relaxed),
+            SendOutcome::Failed => self.failed.fetch_add(1, Ordering::Relaxed),
+        };
+
+        let sample = latency.as_millis() as u64;
+        let prev = self.send_latency_ms.load(Ordering::Relaxed);
+        let next = if prev == UNKNOWN_LATENCY {
+            sample
+        } else {
+            // EWMA, alpha = 0.2
+            (prev * 4 + sample) / 5
+        };
+        self.send_latency_ms.store(next, Ordering::Relaxed);
+    }
+
+    /// 用于排序的延时，优先使用探测结果
+    fn latency_ms(&self) -> u64 {
+        let probe = self.probe_latency_ms.load(Ordering::Relaxed);
+        if probe != UNKNOWN_LATENCY {
+            probe
+        } else {
+            self.send_latency_ms.load(Ordering::Relaxed)
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum SendOutcome {
+    Success,
+    RateLimited,
+    Failed,
+}
+
+/// Jito Block Engine 区域
+pub struct Region {
+    pub endpoint: String,
+    pub sdk: Arc<JitoJsonRpcSDK>,
+    pub stats: RegionStats,
+    active: AtomicBool,
+}
+
+impl Region {
+    pub fn is_active(&self) -> bool {
+        self.active.load(Ordering::Relaxed)
+    }
+}
+
+/// 区域统计快照，用于日志输出
+#[derive(Debug, Clone)]
+pub struct RegionSnapshot {
+    pub endpoint: String,
+    pub active: bool,
+    pub sent: u64,
+    pub success: u64,
+    pub failed: u64,
+    pub rate_limited: u64,
+    pub latency_ms: Option<u64>,
+}
+
+/// bundle 被某个区域接收后的结果
+pub struct BundleReceipt {
+    pub bundle_id: String,
+    pub region: Arc<Region>,
+}
+
+/// 多区域 Jito Block Engine，每个 bundle 并发发送到多个区域
+pub struct JitoRegions {
+    regions: Vec<Arc<Region>>,
+    fan_out: usize,
+    mode: RegionMode,
+    uuid: Option<String>,
+}
+
+impl JitoRegions {
+    pub fn new(config: &JitoConfig, ips: &str) -> Result<Arc<Self>> {
+        let endpoints = config.endpoints();
+        if endpoints.is_empty() {
+            return Err(anyhow!("未配置任何 Jito Block Engine 地址"));
+        }
+
+        let ip_pool = util::parse_ipv4_string(ips)?;
+        let mut regions = Vec::with_capacity(endpoints.len());
+        for endpoint in endpoints {
+            let sdk = if !ip_pool.is_empty() {
+                JitoJsonRpcSDK::new_with_ip_pool(
+                    &endpoint,
+                    config.uuid.clone(),
+                    ip_pool.iter().copied().map(IpAddr::V4).collect(),
+                    JitoAlgorithm::Random,
+                )
+                .map_err(|e| anyhow!("初始化 Jito 区域 {} 失败: {}", endpoint, e))?
+            } else {
+                JitoJsonRpcSDK::new(&endpoint, config.uuid.clone())
+            };
+
+            regions.push(Arc::new(Region {
+                endpoint,
+                sdk: Arc::new(sdk),
+                stats: RegionStats::default(),
+                active: AtomicBool::new(true),
+            }));
+        }
+
+        Ok(Arc::new(Self {
+            regions,
+            fan_out: config.fan_out,
+            mode: config.region_mode,
+            uuid: config.uuid.clone(),
+        }))
+    }
+
+    pub fn regions(&self) -> &[Arc<Region>] {
+        &self.regions
+    }
+
+    /// 本次 bundle 需要发送的区域：活跃区域按延时排序后取前 fan_out 个
+    fn targets(&self) -> Vec<Arc<Region>> {
+        let latencies: Vec<Option<u64>> = self
+            .regions
+            .iter()
+            .map(|r| r.is_active().then(|| r.stats.latency_ms()))
+            .collect();
+
+        select_regions(&latencies, self.fan_out)
+            .into_iter()
+            .map(|idx| Arc::clone(&self.regions[idx]))
+            .collect()
+    }
+
+    /// 并发发送 bundle 到多个区域，返回第一个成功接收的区域
+    pub async fn send_bundle(&self, params: Value) -> Result<BundleReceipt> {
+        let targets = self.targets();
+        let (tx, mut rx) = mpsc::channel(targets.len().max(1));
+
+        for region in targets {
+            let tx = tx.clone();
+            let params = params.clone();
+            let uuid = self.uuid.clone();
+            tokio::spawn(async move {
+                let start = Instant::now();
+                let result = region.sdk.send_bundle(Some(params), uuid.as_deref()).await;
+                let elapsed = start.elapsed();
+
+                let result = match result {
+                    Ok(res) => match res.get("result").and_then(Value::as_str) {
+                        Some(bundle_id) => {
+                            region.stats.record_send(elapsed, SendOutcome::Success);
+                            Ok(bundle_id.to_string())
+                        }
+                        None => {
+                            let outcome = if is_rate_limited(&res) {
+                                SendOutcome::RateLimited
+                            } else {
+                                SendOutcome::Failed
+                            };
+                            region.stats.record_send(elapsed, outcome);
+                            Err(anyhow!("[{}] {}", region.endpoint, res))
+                        }
+                    },
+                    Err(e) => {
+                        let outcome = if is_rate_limited_error(&e.to_string()) {
+                            SendOutcome::RateLimited
+                        } else {
+                            SendOutcome::Failed
+                        };
+                        region.stats.record_send(elapsed, outcome);
+                        Err(anyhow!("[{}] {}", region.endpoint, e))
+                    }
+                };
+
+                debug!(
+                    "Jito 区域 {} 发送耗时 {:.4?}, 结果: {:?}",
+                    region.endpoint,
+                    elapsed,
+                    result.as_ref().map(|_| "ok")
+                );
+                _ = tx.send((region, result)).await;
+            });
+        }
+        drop(tx);
+
+        // 任意一个区域成功即返回，其余区域在后台继续完成并记录统计
+        let mut errors = Vec::new();
+        while let Some((region, result)) = rx.recv().await {
+            match result {
+                Ok(bundle_id) => return Ok(BundleReceipt { bundle_id, region }),
+                Err(e) => errors.push(e.to_string()),
+            }
+        }
+
+        Err(anyhow!(
+            "❌ 所有 Jito 区域发送 bundle 失败: {}",
+            errors.join("; ")
+        ))
+    }
+
+    /// 后台定期探测各区域延时，auto 模式下只保留延时最低的区域
+    pub fn start_probe(self: &Arc<Self>, interval: Duration) {
+        if self.regions.len() < 2 {
+            return;
+        }
+
+        let regions = Arc::clone(self);
+        let interval = interval.max(MIN_PROBE_INTERVAL);
+        tokio::spawn(async move {
+            loop {
+                regions.probe().await;
+                for s in regions.snapshot() {
+                    debug!("Jito 区域状态: {:?}", s);
+                }
+                tokio::time::sleep(interval).await;
+            }
+        });
+    }
+
+    async fn probe(&self) {
+        let mut handles = Vec::with_capacity(self.regions.len());
+        for region in &self.regions {
+            let region = Arc::clone(region);
+            handles.push(tokio::spawn(async move {
+                let start = Instant::now();
+                let latency = match region.sdk.get_tip_accounts().await {
+                    Ok(res) if res.get("result").is_some() => start.elapsed().as_millis() as u64,
+                    Ok(res) => {
+                        warn!("探测 Jito 区域 {} 失败: {}", region.endpoint, res);
+                        UNKNOWN_LATENCY
+                    }
+                    Err(e) => {
+                        warn!("探测 Jito 区域 {} 失败: {}", region.endpoint, e);
+                        UNKNOWN_LATENCY
+                    }
+                };
+                region
+                    .stats
+                    .probe_latency_ms
+                    .store(latency, Ordering::Relaxed);
+            }));
+        }
+        for handle in handles {
+            _ = handle.await;
+        }
+
+        if self.mode != RegionMode::Auto {
+            return;
+        }
+
+        let latencies: Vec<Option<u64>> = self
+            .regions
+            .iter()
+            .map(|r| {
+                let latency = r.stats.probe_latency_ms.load(Ordering::Relaxed);
+                (latency != UNKNOWN_LATENCY).then_some(latency)
+            })
+            .collect();
+
+        let selected = select_regions(&latencies, self.fan_out);
+        // 所有区域都探测失败时保持当前状态
+        if selected.is_empty() {
+            warn!("所有 Jito 区域探测失败，保持当前活跃区域不变");
+            return;
+        }
+
+        for (idx, region) in self.regions.iter().enumerate() {
+            let active = selected.contains(&idx);
+            if region.active.swap(active, Ordering::Relaxed) != active {
+                info!(
+                    "Jito 区域 {} {}",
+                    region.endpoint,
+                    if active { "已启用" } else { "已停用" }
+                );
+            }
+        }
+    }
+
+    pub fn snapshot(&self) -> Vec<RegionSnapshot> {
+        self.regions
+            .iter()
+            .map(|r| {
+                let latency = r.stats.latency_ms();
+                RegionSnapshot {
+                    endpoint: r.endpoint.clone(),
+                    active: r.is_active(),
+                    sent: r.stats.sent.load(Ordering::Relaxed),
+                    success: r.stats.success.load(Ordering::Relaxed),
+                    failed: r.stats.failed.load(Ordering::Relaxed),
+                    rate_limited: r.stats.rate_limited.load(Ordering::Relaxed),
+                    latency_ms: (latency != UNKNOWN_LATENCY).then_some(latency),
+                }
+            })
+            .collect()
+    }
+}
+
+/// 从候选区域中选出延时最低的 fan_out 个（0 表示全部）
+///
+/// `latencies[i]` 为 None 表示该区域不可用；延时未知的区域排在最后并保持配置顺序
+fn select_regions(latencies: &[Option<u64>], fan_out: usize) -> Vec<usize> {
+    let mut candidates: Vec<(usize, u64)> = latencies
+        .iter()
+        .enumerate()
+        .filter_map(|(idx, latency)| latency.map(|l| (idx, l)))
+        .collect();
+    candidates.sort_by_key(|&(idx, latency)| (latency, idx));
+
+    let limit = if fan_out == 0 {
+        candidates.len()
+    } else {
+        fan_out
+    };
+    candidates
+        .into_iter()
+        .take(limit)
+        .map(|(idx, _)| idx)
+        .collect()
+}
+
+/// 根据 JSON-RPC 响应判断是否被限流
+fn is_rate_limited(res: &Value) -> bool {
+    match res.get("error") {
+        Some(error) => {
+            error.get("code").and_then(Value::as_i64) == Some(RATE_LIMITED_CODE)
+                || error
+                    .get("message")
+                    .and_then(Value::as_str)
+                    .is_some_and(is_rate_limited_error)
+        }
+        None => false,
+    }
+}
+
+fn is_rate_limited_error(message: &str) -> bool {
+    let message = message.to_lowercase();
+    message.contains("429") || message.contains("rate limit")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_select_regions() {
+        let latencies = [Some(80), None, Some(20), Some(UNKNOWN_LATENCY), Some(50)];
+        assert_eq!(select_regions(&latencies, 2), vec![2, 4]);
+        assert_eq!(select_regions(&latencies, 0), vec![2, 4, 0, 3]);
+        assert!(select_regions(&[None, None], 3).is_empty());
+    }
+
+    #[test]
+    fn test_is_rate_limited() {
+        let res = json!({
+            "jsonrpc": "2.0",
+            "error": {"code": -32097, "message": "Network congested. Endpoint is globally rate limited."},
+            "id": 1
+        });
+        assert!(is_rate_limited(&res));
+
+        let res = json!({"jsonrpc": "2.0", "error": {"code": -32602, "message": "bundle contains an already processed transaction"}, "id": 1});
+        assert!(!is_rate_limited(&res));
+
+        let res = json!({"jsonrpc": "2.0", "result": "abc", "id": 1});
+        assert!(!is_rate_limited(&res));
+
+        assert!(is_rate_limited_error("HTTP status 429 Too Many Requests"));
+    }
+}
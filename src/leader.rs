@@ -0,0 +1,263 @@
+use crate::blockhash::LatestBlockhash;
+use crate::config::LeaderConfig;
+use anyhow::{Result, anyhow};
+use serde::Deserialize;
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::pubkey::Pubkey;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::str::FromStr;
+use std::sync::{Arc, RwLock};
+use tokio::time::{Duration, Instant};
+use tracing::{debug, error, info};
+
+/// 每个 slot 约 400ms
+pub const SLOT_DURATION: Duration = Duration::from_millis(400);
+
+/// 检查是否进入新 epoch 的间隔
+const EPOCH_CHECK_INTERVAL: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Deserialize)]
+struct KobeValidators {
+    validators: Vec<KobeValidator>,
+}
+
+#[derive(Debug, Deserialize)]
+struct KobeValidator {
+    vote_account: String,
+    #[serde(default)]
+    running_jito: bool,
+}
+
+#[derive(Debug, Default)]
+struct Schedule {
+    /// 当前及下一个 epoch 内每个 slot 的 Leader(节点 identity)
+    leaders: BTreeMap<u64, Pubkey>,
+    /// 下一个 epoch 的第一个 slot，到达后重新加载
+    next_epoch_slot: u64,
+    /// 运行 Jito 客户端的节点 identity
+    jito_validators: HashSet<Pubkey>,
+}
+
+/// 根据提交时所处的 Leader 决定提交通道
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Route {
+    /// Jito Leader 即将出块，通过 bundle 提交
+    Jito,
+    /// 通过 RPC、TPU 等非 Jito 通道提交
+    Fallback,
+    /// 等待 n 个 slot 后通过 bundle 提交
+    Delay(u64),
+    /// 近期没有 Jito Leader，且没有其它通道
+    Drop,
+}
+
+/// 跟踪 leader schedule 及运行 Jito 客户端的验证者
+pub struct LeaderTracker {
+    schedule: RwLock<Schedule>,
+    latest_blockhash: Arc<LatestBlockhash>,
+    config: LeaderConfig,
+}
+
+impl LeaderTracker {
+    /// 加载当前及下一个 epoch 的 leader schedule 及 Jito 验证者，定期刷新，进入新的 epoch 时立即刷新
+    pub async fn start(
+        rpc_client: Arc<RpcClient>,
+        latest_blockhash: Arc<LatestBlockhash>,
+        config: &LeaderConfig,
+    ) -> Result<Arc<Self>> {
+        let tracker = Arc::new(Self {
+            schedule: RwLock::new(Schedule::default()),
+            latest_blockhash,
+            config: config.clone(),
+        });
+        tracker.refresh(&rpc_client).await?;
+
+        let refresher = Arc::clone(&tracker);
+        let interval = config.refresh_interval_to_duration();
+        tokio::spawn(async move {
+            let mut refreshed_at = Instant::now();
+            loop {
+                tokio::time::sleep(EPOCH_CHECK_INTERVAL.min(interval)).await;
+                let next_epoch_slot = refresher.schedule.read().unwrap().next_epoch_slot;
+                let epoch_changed = refresher.latest_blockhash.slot() >= next_epoch_slot;
+                if !epoch_changed && refreshed_at.elapsed() < interval {
+                    continue;
+                }
+                // 刷新失败时在下一次检查时重试
+                match refresher.refresh(&rpc_client).await {
+                    Ok(()) => refreshed_at = Instant::now(),
+                    Err(e) => error!("刷新 leader schedule 失败: {}", e),
+                }
+            }
+        });
+
+        Ok(tracker)
+    }
+
+    async fn refresh(&self, rpc_client: &RpcClient) -> Result<()> {
+        let epoch_info = rpc_client.get_epoch_info().await?;
+        let first_slot = epoch_info.absolute_slot - epoch_info.slot_index;
+        let next_epoch_slot = first_slot + epoch_info.slots_in_epoch;
+
+        let schedule = rpc_client
+            .get_leader_schedule(Some(epoch_info.absolute_slot))
+            .await?
+            .ok_or_else(|| anyhow!("epoch {} 的 leader schedule 不存在", epoch_info.epoch))?;
+        let mut leaders = absolute_leader_slots(first_slot, &schedule);
+
+        // 下一个 epoch 的 leader schedule 在当前 epoch 内已经确定，提前加载，
+        // 临近 epoch 结束时仍能找到下一个 epoch 开始的 Jito Leader
+        match rpc_client.get_leader_schedule(Some(next_epoch_slot)).await {
+            Ok(Some(next)) => leaders.extend(absolute_leader_slots(next_epoch_slot, &next)),
+            Ok(None) => debug!("epoch {} 的 leader schedule 尚未确定", epoch_info.epoch + 1),
+            Err(e) => error!("获取下一个 epoch 的 leader schedule 失败: {}", e),
+        }
+
+        // Jito 验证者列表使用 vote account，需要转换为节点 identity
+        let vote_accounts = rpc_client.get_vote_accounts().await?;
+        let identities: HashMap<String, String> = vote_accounts
+            .current
+            .iter()
+            .chain(vote_accounts.delinquent.iter())
+            .map(|v| (v.vote_pubkey.clone(), v.node_pubkey.clone()))
+            .collect();
+
+        let validators = reqwest::Client::new()
+            .get(&self.config.validators_api)
+            .timeout(Duration::from_secs(10))
+            .send()
+            .await?
+            .error_for_status()?
+            .json::<KobeValidators>()
+            .await?;
+        let jito_validators: HashSet<Pubkey> = validators
+            .validators
+            .iter()
+            .filter(|v| v.running_jito)
+            .filter_map(|v| identities.get(&v.vote_account))
+            .filter_map(|identity| Pubkey::from_str(identity).ok())
+            .collect();
+
+        info!(
+            "epoch {} leader schedule 已加载: {} 个 slot，Jito 验证者 {} 个",
+            epoch_info.epoch,
+            leaders.len(),
+            jito_validators.len()
+        );
+
+        let mut guard = self.schedule.write().unwrap();
+        guard.leaders = leaders;
+        guard.next_epoch_slot = next_epoch_slot;
+        guard.jito_validators = jito_validators;
+        Ok(())
+    }
+
+    /// 距离下一个 Jito Leader 的 slot 数，超出 max_slots 时返回 None
+    pub fn slots_until_jito_leader(&self, max_slots: u64) -> Option<u64> {
+        let slot = self.latest_blockhash.slot();
+        let schedule = self.schedule.read().unwrap();
+        slots_until_jito_leader(&schedule, slot, max_slots)
+    }
+
+    /// urgent: 是否需要立即提交; has_fallback: 是否存在非 Jito 的提交通道
+    pub fn route(&self, urgent: bool, has_fallback: bool) -> Route {
+        let max_slots = self.config.lookahead_slots.max(self.config.max_delay_slots);
+        let next = self.slots_until_jito_leader(max_slots);
+        debug!("距离下一个 Jito Leader: {:?} slots", next);
+        route(next, &self.config, urgent, has_fallback)
+    }
+}
+
+/// 将 leader schedule 中相对 epoch 起始位置的 slot 转换为绝对 slot
+fn absolute_leader_slots(
+    first_slot: u64,
+    schedule: &HashMap<String, Vec<usize>>,
+) -> BTreeMap<u64, Pubkey> {
+    schedule
+        .iter()
+        .filter_map(|(identity, slots)| Some((Pubkey::from_str(identity).ok()?, slots)))
+        .flat_map(|(identity, slots)| {
+            slots
+                .iter()
+                .map(move |idx| (first_slot + *idx as u64, identity))
+        })
+        .collect()
+}
+
+fn slots_until_jito_leader(schedule: &Schedule, slot: u64, max_slots: u64) -> Option<u64> {
+    schedule
+        .leaders
+        .range(slot..=slot + max_slots)
+        .find(|(_, leader)| schedule.jito_validators.contains(leader))
+        .map(|(leader_slot, _)| leader_slot - slot)
+}
+
+fn route(next_jito: Option<u64>, config: &LeaderConfig, urgent: bool, has_fallback: bool) -> Route {
+    match next_jito {
+        Some(distance) if distance <= config.lookahead_slots => Route::Jito,
+        _ if urgent && has_fallback => Route::Fallback,
+        Some(distance) if distance <= config.max_delay_slots => {
+            Route::Delay(distance - config.lookahead_slots)
+        }
+        _ if has_fallback => Route::Fallback,
+        _ => Route::Drop,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> LeaderConfig {
+        LeaderConfig {
+            enabled: true,
+            lookahead_slots: 2,
+            max_delay_slots: 8,
+            urgent_profit_amount: 0,
+            refresh_interval_ms: 60_000,
+            validators_api: String::new(),
+        }
+    }
+
+    #[test]
+    fn test_slots_until_jito_leader() {
+        let jito = Pubkey::new_unique();
+        let other = Pubkey::new_unique();
+        let schedule = HashMap::from([
+            (other.to_string(), vec![0, 1, 2, 3]),
+            (jito.to_string(), vec![4, 5, 6, 7]),
+        ]);
+        let mut schedule = Schedule {
+            leaders: absolute_leader_slots(1_000, &schedule),
+            next_epoch_slot: 1_008,
+            jito_validators: HashSet::from([jito]),
+        };
+
+        assert_eq!(slots_until_jito_leader(&schedule, 1_001, 10), Some(3));
+        assert_eq!(slots_until_jito_leader(&schedule, 1_005, 10), Some(0));
+        assert_eq!(slots_until_jito_leader(&schedule, 1_000, 2), None);
+        // 超出已加载 epoch 的 slot
+        assert_eq!(slots_until_jito_leader(&schedule, 1_008, 10), None);
+
+        // 加载下一个 epoch 后可以跨 epoch 查找
+        let next = HashMap::from([
+            (other.to_string(), vec![0, 1]),
+            (jito.to_string(), vec![2, 3]),
+        ]);
+        schedule
+            .leaders
+            .extend(absolute_leader_slots(schedule.next_epoch_slot, &next));
+        assert_eq!(slots_until_jito_leader(&schedule, 1_008, 10), Some(2));
+    }
+
+    #[test]
+    fn test_route() {
+        let config = config();
+        assert_eq!(route(Some(1), &config, false, false), Route::Jito);
+        assert_eq!(route(Some(5), &config, true, true), Route::Fallback);
+        assert_eq!(route(Some(5), &config, false, true), Route::Delay(3));
+        assert_eq!(route(Some(5), &config, true, false), Route::Delay(3));
+        assert_eq!(route(None, &config, false, true), Route::Fallback);
+        assert_eq!(route(None, &config, false, false), Route::Drop);
+    }
+}
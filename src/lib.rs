@@ -1,9 +1,23 @@
+pub mod balance;
 pub mod blockhash;
+pub mod broadcast;
+pub mod bundle_simulation;
 pub mod config;
+pub mod confirmation;
 pub mod constants;
 pub mod engine;
 pub mod error;
 pub mod flashloan;
 pub mod http_client;
+pub mod jito;
+pub mod leader;
+pub mod nonce;
+pub mod quote_guard;
+pub mod race;
+pub mod rate_limiter;
+pub mod rebroadcast;
+pub mod sender;
+pub mod tpu;
+pub mod tx_builder;
 pub mod types;
 pub mod util;
@@ -3,6 +3,8 @@ use anyhow::{Result, anyhow};
 use arbitrage_bot::*;
 use clap::{Parser, Subcommand};
 use self_update::Status as UpdateStatus;
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::{commitment_config::CommitmentConfig, pubkey::Pubkey, signer::Signer};
 use std::str::FromStr;
 use std::sync::Arc;
 use tokio::{
@@ -38,6 +40,52 @@ enum Commands {
 
     /// 初始化配置文件
     Init,
+
+    /// 管理持久 nonce 账户
+    Nonce {
+        #[command(subcommand)]
+        command: NonceCommands,
+    },
+
+    /// 管理闪电贷账户
+    Flashloan {
+        #[command(subcommand)]
+        command: FlashloanCommands,
+    },
+}
+
+#[derive(Subcommand)]
+enum NonceCommands {
+    /// 创建 nonce 账户，authority 为当前钱包
+    Create,
+
+    /// 查看 nonce 账户，不指定时使用配置文件中的 nonce.account
+    Show { account: Option<String> },
+
+    /// 推进 nonce，使之前使用该 nonce 签名的交易全部失效
+    Advance { account: Option<String> },
+
+    /// 关闭 nonce 账户并取回全部余额
+    Close { account: Option<String> },
+}
+
+#[derive(Subcommand)]
+enum FlashloanCommands {
+    /// 创建 MarginFi 闪电贷使用的 marginfi 账户，authority 为当前钱包
+    MarginfiInit {
+        /// 不指定时使用配置文件中的 flash_loan.marginfi_group
+        #[arg(long)]
+        group: Option<String>,
+    },
+
+    /// 查看 Kamino reserve 的可借数量、借款上限、闪电贷费率等信息
+    Inspect {
+        reserve: String,
+
+        /// 以 JSON 格式输出
+        #[arg(long)]
+        json: bool,
+    },
 }
 
 #[tokio::main]
@@ -85,6 +133,97 @@ async fn main() -> Result<()> {
         Commands::Init => {
             init_config()?;
         }
+
+        Commands::Nonce { command } => {
+            manage_nonce(command).await?;
+        }
+
+        Commands::Flashloan { command } => {
+            manage_flashloan(command).await?;
+        }
+    }
+    Ok(())
+}
+
+async fn manage_nonce(command: NonceCommands) -> Result<()> {
+    let config = config::get_config();
+    let rpc_client =
+        RpcClient::new_with_commitment(config.rpc_endpoint.clone(), CommitmentConfig::confirmed());
+    let payer = config.keypair();
+
+    let nonce_account = |account: Option<String>| -> Result<Pubkey> {
+        let account = account.unwrap_or_else(|| config.nonce.account.clone());
+        Pubkey::from_str(&account).map_err(|e| anyhow!("无效的 nonce 账户 {}: {}", account, e))
+    };
+
+    match command {
+        NonceCommands::Create => {
+            let (account, signature) = nonce::create_nonce_account(&rpc_client, &payer).await?;
+            println!("✅ nonce 账户已创建: {}", account);
+            println!("   交易: https://solscan.io/tx/{}", signature);
+            println!("   请将其填写到配置文件的 nonce.account 中");
+        }
+        NonceCommands::Show { account } => {
+            let account = nonce_account(account)?;
+            let data = nonce::fetch_nonce_data(&rpc_client, &account).await?;
+            let balance = rpc_client.get_balance(&account).await?;
+            println!("nonce 账户: {}", account);
+            println!("authority: {}", data.authority);
+            println!("nonce: {}", data.blockhash());
+            println!("余额: {} lamports", balance);
+            if data.authority != payer.pubkey() {
+                println!("⚠️  authority 与当前钱包 {} 不一致", payer.pubkey());
+            }
+        }
+        NonceCommands::Advance { account } => {
+            let account = nonce_account(account)?;
+            let signature = nonce::advance_nonce_account(&rpc_client, &payer, &account).await?;
+            println!("✅ nonce 已推进: https://solscan.io/tx/{}", signature);
+        }
+        NonceCommands::Close { account } => {
+            let account = nonce_account(account)?;
+            let signature = nonce::close_nonce_account(&rpc_client, &payer, &account).await?;
+            println!("✅ nonce 账户已关闭: https://solscan.io/tx/{}", signature);
+        }
+    }
+    Ok(())
+}
+
+async fn manage_flashloan(command: FlashloanCommands) -> Result<()> {
+    let config = config::get_config();
+    let rpc_client =
+        RpcClient::new_with_commitment(config.rpc_endpoint.clone(), CommitmentConfig::confirmed());
+
+    match command {
+        FlashloanCommands::MarginfiInit { group } => {
+            let payer = config.keypair();
+            let group = group.unwrap_or_else(|| {
+                config
+                    .flash_loan
+                    .as_ref()
+                    .map(|fl| fl.marginfi_group.clone())
+                    .unwrap_or_else(|| flashloan::marginfi::MARGINFI_GROUP.to_string())
+            });
+            let group =
+                Pubkey::from_str(&group).map_err(|e| anyhow!("无效的 group {}: {}", group, e))?;
+            let (account, signature) =
+                flashloan::marginfi::create_marginfi_account(&rpc_client, &payer, &group).await?;
+            println!("✅ marginfi 账户已创建: {}", account);
+            println!("   交易: https://solscan.io/tx/{}", signature);
+            println!("   请将其填写到配置文件的 flash_loan.marginfi_account 中");
+            println!("   该账户应只用于闪电贷，不要存入或借出其它资产");
+        }
+        FlashloanCommands::Inspect { reserve, json } => {
+            let reserve = Pubkey::from_str(&reserve)
+                .map_err(|e| anyhow!("无效的 reserve {}: {}", reserve, e))?;
+            let state = flashloan::KaminoState::fetch(&rpc_client, reserve).await?;
+            let report = flashloan::inspect::ReserveReport::from(&state);
+            if json {
+                println!("{}", serde_json::to_string_pretty(&report)?);
+            } else {
+                println!("{}", report);
+            }
+        }
     }
     Ok(())
 }
@@ -94,7 +233,7 @@ async fn run() -> Result<()> {
         "🚀 启动主程序[{}]...",
         chrono::Local::now().format("%Y-%m-%d %H:%M:%S")
     );
-    let mut bot = engine::Engine::new().await;
+    let mut bot = engine::Engine::new().await?;
 
     let config = config::get_config();
 
@@ -120,6 +259,7 @@ async fn run() -> Result<()> {
         tokio::select! {
             _ = shutdown.notified() => {
                 println!("🔌 收到停止服务信号，正在退出...");
+                bot.shutdown();
                 break;
             }
             _ = async {
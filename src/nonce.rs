@@ -0,0 +1,233 @@
+use crate::confirmation::{ConfirmationEvent, Outcome};
+use anyhow::{Result, anyhow};
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::{
+    hash::Hash,
+    instruction::Instruction,
+    nonce::state::{Data, State, Versions},
+    pubkey::Pubkey,
+    signature::{Keypair, Signature},
+    signer::Signer,
+    system_instruction,
+    transaction::Transaction,
+};
+use solana_sdk_ids::system_program;
+use std::sync::Arc;
+use tokio::{
+    sync::{Notify, RwLock},
+    time::Duration,
+};
+use tracing::error;
+
+/// 使用持久 nonce 代替 recent blockhash
+///
+/// 同一个 nonce 值只能被一笔交易使用，多个通道提交的交易最多只有一笔能上链；
+/// nonce 不会过期，交易可以提前签名
+pub struct DurableNonce {
+    account: Pubkey,
+    authority: Pubkey,
+    /// 当前的 nonce 值
+    value: RwLock<Hash>,
+    /// 交易上链后立即刷新，不等待下一次轮询
+    refresh: Notify,
+}
+
+impl DurableNonce {
+    /// 加载 nonce 账户并定期刷新 nonce 值，authority 需要与账户中记录的一致
+    ///
+    /// 除定期轮询外，on_confirmation 收到交易上链事件时也会立即刷新
+    pub async fn start(
+        rpc_client: Arc<RpcClient>,
+        account: Pubkey,
+        authority: Pubkey,
+        interval: Duration,
+    ) -> Result<Arc<Self>> {
+        let data = fetch_nonce_data(&rpc_client, &account).await?;
+        if data.authority != authority {
+            return Err(anyhow!(
+                "nonce 账户 {} 的 authority 为 {}，与当前钱包 {} 不一致",
+                account,
+                data.authority,
+                authority
+            ));
+        }
+
+        let nonce = Arc::new(Self {
+            account,
+            authority,
+            value: RwLock::new(data.blockhash()),
+            refresh: Notify::new(),
+        });
+
+        let refresher = Arc::clone(&nonce);
+        tokio::spawn(async move {
+            loop {
+                tokio::select! {
+                    _ = tokio::time::sleep(interval) => {}
+                    _ = refresher.refresh.notified() => {}
+                }
+                match fetch_nonce_data(&rpc_client, &refresher.account).await {
+                    Ok(data) => *refresher.value.write().await = data.blockhash(),
+                    Err(e) => error!("刷新 nonce 失败: {}", e),
+                }
+            }
+        });
+
+        Ok(nonce)
+    }
+
+    /// 交易确认事件回调
+    ///
+    /// 使用 nonce 的交易上链后(无论执行成功与否)nonce 都已推进，缓存的值随之失效，
+    /// 立即重新读取，避免下一笔交易使用旧值签名
+    pub fn on_confirmation(&self, event: &ConfirmationEvent) {
+        if matches!(
+            event.outcome,
+            Outcome::Landed { .. } | Outcome::Failed { .. }
+        ) {
+            self.refresh.notify_one();
+        }
+    }
+
+    pub async fn get_nonce(&self) -> Hash {
+        *self.value.read().await
+    }
+
+    /// 推进 nonce 的指令，必须是交易的第一条指令
+    pub fn advance_instruction(&self) -> Instruction {
+        system_instruction::advance_nonce_account(&self.account, &self.authority)
+    }
+}
+
+/// 读取并解析 nonce 账户
+pub async fn fetch_nonce_data(rpc_client: &RpcClient, account: &Pubkey) -> Result<Data> {
+    let account_data = rpc_client
+        .get_account(account)
+        .await
+        .map_err(|e| anyhow!("获取 nonce 账户 {} 失败: {}", account, e))?;
+    if account_data.owner != system_program::ID {
+        return Err(anyhow!("{} 不是 nonce 账户", account));
+    }
+    parse_nonce_data(&account_data.data)
+}
+
+fn parse_nonce_data(data: &[u8]) -> Result<Data> {
+    let versions: Versions =
+        bincode::deserialize(data).map_err(|e| anyhow!("nonce 账户数据解析失败: {}", e))?;
+    match versions.state() {
+        State::Initialized(data) => Ok(data.clone()),
+        State::Uninitialized => Err(anyhow!("nonce 账户未初始化")),
+    }
+}
+
+/// 创建 nonce 账户，authority 为 payer
+pub async fn create_nonce_account(
+    rpc_client: &RpcClient,
+    payer: &Keypair,
+) -> Result<(Pubkey, Signature)> {
+    let nonce_keypair = Keypair::new();
+    let lamports = rpc_client
+        .get_minimum_balance_for_rent_exemption(State::size())
+        .await?;
+    let ixs = system_instruction::create_nonce_account(
+        &payer.pubkey(),
+        &nonce_keypair.pubkey(),
+        &payer.pubkey(),
+        lamports,
+    );
+    let signature = send_instructions(rpc_client, payer, &ixs, &[payer, &nonce_keypair]).await?;
+    Ok((nonce_keypair.pubkey(), signature))
+}
+
+/// 手动推进 nonce，使之前使用该 nonce 签名的交易全部失效
+pub async fn advance_nonce_account(
+    rpc_client: &RpcClient,
+    payer: &Keypair,
+    account: &Pubkey,
+) -> Result<Signature> {
+    let ix = system_instruction::advance_nonce_account(account, &payer.pubkey());
+    send_instructions(rpc_client, payer, &[ix], &[payer]).await
+}
+
+/// 取回 nonce 账户的全部余额，账户随之关闭
+pub async fn close_nonce_account(
+    rpc_client: &RpcClient,
+    payer: &Keypair,
+    account: &Pubkey,
+) -> Result<Signature> {
+    let lamports = rpc_client.get_balance(account).await?;
+    let ix = system_instruction::withdraw_nonce_account(
+        account,
+        &payer.pubkey(),
+        &payer.pubkey(),
+        lamports,
+    );
+    send_instructions(rpc_client, payer, &[ix], &[payer]).await
+}
+
+async fn send_instructions(
+    rpc_client: &RpcClient,
+    payer: &Keypair,
+    ixs: &[Instruction],
+    signers: &[&Keypair],
+) -> Result<Signature> {
+    let recent_blockhash = rpc_client.get_latest_blockhash().await?;
+    let tx =
+        Transaction::new_signed_with_payer(ixs, Some(&payer.pubkey()), signers, recent_blockhash);
+    rpc_client
+        .send_and_confirm_transaction(&tx)
+        .await
+        .map_err(|e| anyhow!("{}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use solana_sdk::nonce::state::DurableNonce as NonceValue;
+
+    #[test]
+    fn test_parse_nonce_data() {
+        let authority = Pubkey::new_unique();
+        let durable_nonce = NonceValue::from_blockhash(&Hash::new_unique());
+        let data = Data::new(authority, durable_nonce, 5000);
+
+        let bytes = bincode::serialize(&Versions::new(State::Initialized(data.clone()))).unwrap();
+        let parsed = parse_nonce_data(&bytes).unwrap();
+        assert_eq!(parsed.authority, authority);
+        assert_eq!(parsed.blockhash(), data.blockhash());
+
+        let bytes = bincode::serialize(&Versions::new(State::Uninitialized)).unwrap();
+        assert!(parse_nonce_data(&bytes).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_refresh_on_confirmation() {
+        let nonce = DurableNonce {
+            account: Pubkey::new_unique(),
+            authority: Pubkey::new_unique(),
+            value: RwLock::new(Hash::new_unique()),
+            refresh: Notify::new(),
+        };
+        let event = |outcome| ConfirmationEvent {
+            signature: Signature::new_unique(),
+            label: "rpc".to_string(),
+            outcome,
+            elapsed: Duration::ZERO,
+        };
+        let notified = || tokio::time::timeout(Duration::from_millis(10), nonce.refresh.notified());
+
+        // 未上链的交易不会推进 nonce
+        nonce.on_confirmation(&event(Outcome::Expired));
+        assert!(notified().await.is_err());
+
+        nonce.on_confirmation(&event(Outcome::Landed { slot: 1 }));
+        assert!(notified().await.is_ok());
+
+        // 执行失败的交易同样推进了 nonce
+        nonce.on_confirmation(&event(Outcome::Failed {
+            slot: 2,
+            error: solana_sdk::transaction::TransactionError::AccountNotFound,
+        }));
+        assert!(notified().await.is_ok());
+    }
+}
@@ -0,0 +1,115 @@
+use crate::config::QuoteGuardConfig;
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// 报价被拒绝的原因
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Rejection {
+    /// 报价的 context_slot 落后当前 slot 过多
+    Stale { lag: u64 },
+    /// 两次报价的 context_slot 相差过多
+    LegSpan { span: u64 },
+}
+
+impl fmt::Display for Rejection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Rejection::Stale { lag } => write!(f, "报价落后当前 slot {} 个", lag),
+            Rejection::LegSpan { span } => write!(f, "两次报价相差 {} 个 slot", span),
+        }
+    }
+}
+
+/// 根据报价的 context_slot 判断报价是否过时
+#[derive(Debug, Default)]
+pub struct QuoteGuard {
+    config: QuoteGuardConfig,
+    stale: AtomicU64,
+    leg_span: AtomicU64,
+}
+
+impl QuoteGuard {
+    pub fn new(config: &QuoteGuardConfig) -> Self {
+        Self {
+            config: config.clone(),
+            ..Default::default()
+        }
+    }
+
+    /// current_slot 为 0(尚未获取到 slot)时只比较两次报价
+    pub fn check(
+        &self,
+        current_slot: u64,
+        leg1_slot: u64,
+        leg2_slot: u64,
+    ) -> Result<(), Rejection> {
+        let result = check(&self.config, current_slot, leg1_slot, leg2_slot);
+        match result {
+            Err(Rejection::Stale { .. }) => self.stale.fetch_add(1, Ordering::Relaxed),
+            Err(Rejection::LegSpan { .. }) => self.leg_span.fetch_add(1, Ordering::Relaxed),
+            Ok(()) => 0,
+        };
+        result
+    }
+
+    /// (报价过时次数, 两次报价相差过多次数)
+    pub fn snapshot(&self) -> (u64, u64) {
+        (
+            self.stale.load(Ordering::Relaxed),
+            self.leg_span.load(Ordering::Relaxed),
+        )
+    }
+}
+
+fn check(
+    config: &QuoteGuardConfig,
+    current_slot: u64,
+    leg1_slot: u64,
+    leg2_slot: u64,
+) -> Result<(), Rejection> {
+    // 报价节点的 slot 可能领先于本地，只计算落后的部分
+    let lag = current_slot.saturating_sub(leg1_slot.min(leg2_slot));
+    if config.max_slot_lag > 0 && current_slot > 0 && lag > config.max_slot_lag {
+        return Err(Rejection::Stale { lag });
+    }
+
+    let span = leg1_slot.abs_diff(leg2_slot);
+    if config.max_leg_slot_span > 0 && span > config.max_leg_slot_span {
+        return Err(Rejection::LegSpan { span });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check() {
+        let guard = QuoteGuard::new(&QuoteGuardConfig {
+            max_slot_lag: 10,
+            max_leg_slot_span: 3,
+        });
+
+        assert_eq!(guard.check(1_000, 995, 997), Ok(()));
+        // 报价领先于本地 slot
+        assert_eq!(guard.check(1_000, 1_002, 1_001), Ok(()));
+        assert_eq!(
+            guard.check(1_000, 985, 995),
+            Err(Rejection::Stale { lag: 15 })
+        );
+        assert_eq!(
+            guard.check(1_000, 990, 995),
+            Err(Rejection::LegSpan { span: 5 })
+        );
+        // 尚未获取到 slot
+        assert_eq!(guard.check(0, 990, 991), Ok(()));
+        assert_eq!(guard.snapshot(), (1, 1));
+
+        let guard = QuoteGuard::new(&QuoteGuardConfig {
+            max_slot_lag: 0,
+            max_leg_slot_span: 0,
+        });
+        assert_eq!(guard.check(1_000, 100, 900), Ok(()));
+    }
+}
@@ -0,0 +1,145 @@
+use crate::confirmation::{ConfirmationTracker, Outcome};
+use solana_sdk::signature::Signature;
+use std::collections::{HashMap, HashSet};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+use tokio::sync::broadcast::error::RecvError;
+use tracing::info;
+
+/// 同一个套利通过多个通道同时提交时，统计最终由哪个通道上链
+///
+/// 各通道的交易推进同一个持久 nonce，最多只有一笔能上链
+#[derive(Default)]
+pub struct RaceTracker {
+    wins: Mutex<HashMap<String, u64>>,
+    /// 所有通道均未成功上链的次数
+    misses: AtomicU64,
+}
+
+impl RaceTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 等待各通道交易的确认结果，返回成功上链的通道名称
+    ///
+    /// entries: (通道名称, 该通道最后一笔交易的签名)
+    pub async fn track(
+        &self,
+        tracker: &ConfirmationTracker,
+        entries: Vec<(String, Signature)>,
+    ) -> Option<String> {
+        // 先订阅再登记，避免错过事件
+        let mut events = tracker.subscribe();
+        let mut remaining: HashSet<Signature> = HashSet::new();
+        for (name, signature) in entries {
+            tracker.track(signature, name);
+            remaining.insert(signature);
+        }
+
+        let winner = loop {
+            match events.recv().await {
+                Ok(event) if remaining.remove(&event.signature) => {
+                    if matches!(event.outcome, Outcome::Landed { .. }) {
+                        break Some(event.label);
+                    }
+                    if remaining.is_empty() {
+                        break None;
+                    }
+                }
+                Ok(_) | Err(RecvError::Lagged(_)) => {}
+                Err(RecvError::Closed) => break None,
+            }
+        };
+
+        self.record(winner.as_deref());
+        winner
+    }
+
+    fn record(&self, winner: Option<&str>) {
+        match winner {
+            Some(name) => {
+                *self
+                    .wins
+                    .lock()
+                    .unwrap()
+                    .entry(name.to_string())
+                    .or_default() += 1;
+            }
+            None => {
+                self.misses.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+        let (wins, misses) = self.snapshot();
+        info!(
+            "🏁 提交通道胜出: {}，统计: {:?}，均未上链: {}",
+            winner.unwrap_or("无"),
+            wins,
+            misses
+        );
+    }
+
+    pub fn snapshot(&self) -> (HashMap<String, u64>, u64) {
+        (
+            self.wins.lock().unwrap().clone(),
+            self.misses.load(Ordering::Relaxed),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use solana_sdk::transaction::TransactionError;
+    use std::sync::Arc;
+    use tokio::time::Duration;
+
+    #[tokio::test]
+    async fn test_track_winner() {
+        let tracker = Arc::new(ConfirmationTracker::new(Duration::from_secs(1)));
+        let race = Arc::new(RaceTracker::new());
+        let jito = Signature::new_unique();
+        let rpc = Signature::new_unique();
+
+        let handle = {
+            let tracker = Arc::clone(&tracker);
+            let race = Arc::clone(&race);
+            tokio::spawn(async move {
+                race.track(
+                    &tracker,
+                    vec![("jito".to_string(), jito), ("rpc".to_string(), rpc)],
+                )
+                .await
+            })
+        };
+        while tracker.pending_count() < 2 {
+            tokio::task::yield_now().await;
+        }
+
+        tracker.resolve(
+            &jito,
+            Outcome::Failed {
+                slot: 1,
+                error: TransactionError::AccountInUse,
+            },
+        );
+        tracker.resolve(&rpc, Outcome::Landed { slot: 1 });
+
+        assert_eq!(handle.await.unwrap(), Some("rpc".to_string()));
+        assert_eq!(race.snapshot().0["rpc"], 1);
+    }
+
+    #[test]
+    fn test_record() {
+        let race = RaceTracker::new();
+        race.record(Some("jito"));
+        race.record(Some("jito"));
+        race.record(Some("rpc"));
+        race.record(None);
+
+        let (wins, misses) = race.snapshot();
+        assert_eq!(wins["jito"], 2);
+        assert_eq!(wins["rpc"], 1);
+        assert_eq!(misses, 1);
+    }
+}
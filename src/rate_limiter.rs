@@ -0,0 +1,292 @@
+use std::sync::{Arc, Mutex};
+use tokio::sync::{Notify, oneshot};
+use tokio::time::{Duration, Instant};
+use tracing::debug;
+
+#[derive(Debug, thiserror::Error, PartialEq)]
+pub enum LimiterError {
+    #[error("限流队列已满，丢弃利润较低的 bundle (利润 {0})")]
+    Dropped(u64),
+    #[error("等待限流令牌超时 (利润 {0})")]
+    Expired(u64),
+}
+
+/// 令牌桶，按固定速率补充令牌
+#[derive(Debug)]
+pub struct TokenBucket {
+    rate: f64,
+    capacity: f64,
+    state: Mutex<BucketState>,
+}
+
+#[derive(Debug)]
+struct BucketState {
+    tokens: f64,
+    last_refill: Instant,
+}
+
+impl TokenBucket {
+    /// rate: 每秒补充的令牌数; burst: 桶容量
+    pub fn new(rate: f64, burst: u32) -> Self {
+        let capacity = (burst.max(1)) as f64;
+        Self {
+            rate,
+            capacity,
+            state: Mutex::new(BucketState {
+                tokens: capacity,
+                last_refill: Instant::now(),
+            }),
+        }
+    }
+
+    fn refill(&self, state: &mut BucketState) {
+        let now = Instant::now();
+        let elapsed = now.duration_since(state.last_refill).as_secs_f64();
+        state.tokens = (state.tokens + elapsed * self.rate).min(self.capacity);
+        state.last_refill = now;
+    }
+
+    pub fn try_acquire(&self) -> bool {
+        let mut state = self.state.lock().unwrap();
+        self.refill(&mut state);
+        if state.tokens >= 1.0 {
+            state.tokens -= 1.0;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// 归还一个令牌（令牌已取出但未使用）
+    pub fn refund(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.tokens = (state.tokens + 1.0).min(self.capacity);
+    }
+
+    /// 距离下一个令牌可用的时间
+    pub fn time_until_available(&self) -> Duration {
+        let mut state = self.state.lock().unwrap();
+        self.refill(&mut state);
+        if state.tokens >= 1.0 || self.rate <= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64((1.0 - state.tokens) / self.rate)
+        }
+    }
+}
+
+struct Pending {
+    profit: u64,
+    seq: u64,
+    tx: oneshot::Sender<Result<(), LimiterError>>,
+}
+
+struct Queue {
+    pending: Vec<Pending>,
+    seq: u64,
+}
+
+/// bundle 发送限流器
+///
+/// 令牌不足时 bundle 进入等待队列，令牌补充后优先放行利润最高的 bundle；
+/// 队列已满时丢弃利润最低的 bundle
+pub struct BundleLimiter {
+    bucket: Option<TokenBucket>,
+    queue: Mutex<Queue>,
+    max_pending: usize,
+    max_wait: Duration,
+    notify: Notify,
+}
+
+impl BundleLimiter {
+    /// rate 为 0 时不限流
+    pub fn new(rate: f64, burst: u32, max_pending: usize, max_wait: Duration) -> Arc<Self> {
+        let limiter = Arc::new(Self {
+            bucket: (rate > 0.0).then(|| TokenBucket::new(rate, burst)),
+            queue: Mutex::new(Queue {
+                pending: Vec::new(),
+                seq: 0,
+            }),
+            max_pending,
+            max_wait,
+            notify: Notify::new(),
+        });
+
+        if limiter.bucket.is_some() {
+            let dispatcher = Arc::clone(&limiter);
+            tokio::spawn(async move { dispatcher.dispatch().await });
+        }
+
+        limiter
+    }
+
+    /// 获取发送许可，profit 为本次套利的预期利润
+    pub async fn acquire(&self, profit: u64) -> Result<(), LimiterError> {
+        let Some(bucket) = &self.bucket else {
+            return Ok(());
+        };
+
+        let (tx, rx) = oneshot::channel();
+        let seq = {
+            let mut queue = self.queue.lock().unwrap();
+            // 没有排队的 bundle 时直接尝试获取令牌
+            if queue.pending.is_empty() && bucket.try_acquire() {
+                return Ok(());
+            }
+
+            if self.max_pending == 0 {
+                return Err(LimiterError::Dropped(profit));
+            }
+
+            queue.seq += 1;
+            let seq = queue.seq;
+            queue.pending.push(Pending { profit, seq, tx });
+
+            if queue.pending.len() > self.max_pending {
+                let lowest = lowest_profit_index(&queue.pending);
+                let dropped = queue.pending.swap_remove(lowest);
+                debug!("限流队列已满，丢弃利润 {} 的 bundle", dropped.profit);
+                _ = dropped.tx.send(Err(LimiterError::Dropped(dropped.profit)));
+            }
+            seq
+        };
+        self.notify.notify_one();
+
+        match tokio::time::timeout(self.max_wait, rx).await {
+            Ok(Ok(result)) => result,
+            Ok(Err(_)) => Err(LimiterError::Dropped(profit)),
+            Err(_) => {
+                let mut queue = self.queue.lock().unwrap();
+                queue.pending.retain(|p| p.seq != seq);
+                Err(LimiterError::Expired(profit))
+            }
+        }
+    }
+
+    async fn dispatch(&self) {
+        let Some(bucket) = &self.bucket else {
+            return;
+        };
+
+        loop {
+            let has_pending = !self.queue.lock().unwrap().pending.is_empty();
+            if !has_pending {
+                self.notify.notified().await;
+                continue;
+            }
+
+            let wait = bucket.time_until_available();
+            if !wait.is_zero() {
+                tokio::time::sleep(wait).await;
+            }
+            if !bucket.try_acquire() {
+                continue;
+            }
+
+            let next = {
+                let mut queue = self.queue.lock().unwrap();
+                if queue.pending.is_empty() {
+                    None
+                } else {
+                    let highest = highest_profit_index(&queue.pending);
+                    Some(queue.pending.swap_remove(highest))
+                }
+            };
+
+            match next {
+                Some(pending) => {
+                    // 等待方已超时退出，令牌归还
+                    if pending.tx.send(Ok(())).is_err() {
+                        bucket.refund();
+                    }
+                }
+                None => bucket.refund(),
+            }
+        }
+    }
+}
+
+/// 利润最低的 bundle，利润相同时丢弃最新的
+fn lowest_profit_index(pending: &[Pending]) -> usize {
+    pending
+        .iter()
+        .enumerate()
+        .min_by_key(|(_, p)| (p.profit, std::cmp::Reverse(p.seq)))
+        .map(|(idx, _)| idx)
+        .unwrap_or(0)
+}
+
+/// 利润最高的 bundle，利润相同时先到先得
+fn highest_profit_index(pending: &[Pending]) -> usize {
+    pending
+        .iter()
+        .enumerate()
+        .max_by_key(|(_, p)| (p.profit, std::cmp::Reverse(p.seq)))
+        .map(|(idx, _)| idx)
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_token_bucket() {
+        let bucket = TokenBucket::new(1.0, 2);
+        assert!(bucket.try_acquire());
+        assert!(bucket.try_acquire());
+        assert!(!bucket.try_acquire());
+        assert!(bucket.time_until_available() > Duration::ZERO);
+
+        bucket.refund();
+        assert!(bucket.try_acquire());
+    }
+
+    #[tokio::test]
+    async fn test_limiter_disabled() {
+        let limiter = BundleLimiter::new(0.0, 1, 0, Duration::from_millis(10));
+        for profit in 0..10 {
+            assert_eq!(limiter.acquire(profit).await, Ok(()));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_limiter_drops_lowest_profit() {
+        let limiter = BundleLimiter::new(0.001, 1, 1, Duration::from_millis(200));
+        assert_eq!(limiter.acquire(100).await, Ok(()));
+
+        let low = {
+            let limiter = Arc::clone(&limiter);
+            tokio::spawn(async move { limiter.acquire(10).await })
+        };
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        let high = limiter.acquire(50).await;
+
+        assert_eq!(low.await.unwrap(), Err(LimiterError::Dropped(10)));
+        assert_eq!(high, Err(LimiterError::Expired(50)));
+    }
+
+    #[tokio::test]
+    async fn test_limiter_prefers_highest_profit() {
+        let limiter = BundleLimiter::new(20.0, 1, 4, Duration::from_secs(1));
+        assert_eq!(limiter.acquire(1).await, Ok(()));
+
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        for profit in [5, 30, 10] {
+            let limiter = Arc::clone(&limiter);
+            let tx = tx.clone();
+            tokio::spawn(async move {
+                if limiter.acquire(profit).await.is_ok() {
+                    _ = tx.send(profit);
+                }
+            });
+        }
+        drop(tx);
+
+        let mut order = vec![];
+        while let Some(profit) = rx.recv().await {
+            order.push(profit);
+        }
+        assert_eq!(order, vec![30, 10, 5]);
+    }
+}
@@ -0,0 +1,101 @@
+use crate::broadcast::Broadcaster;
+use crate::confirmation::ConfirmationTracker;
+use solana_client::{nonblocking::rpc_client::RpcClient, rpc_config::RpcSendTransactionConfig};
+use solana_sdk::transaction::VersionedTransaction;
+use std::sync::Arc;
+use tokio::time::Duration;
+use tracing::{debug, warn};
+
+/// 最小重发间隔，避免 interval_ms 配置过小时不停重发
+const MIN_INTERVAL: Duration = Duration::from_millis(200);
+
+/// 重发配置：由程序自行重发，不依赖 RPC 节点的重试
+const RESEND_CONFIG: RpcSendTransactionConfig = RpcSendTransactionConfig {
+    skip_preflight: true,
+    preflight_commitment: None,
+    encoding: None,
+    max_retries: Some(0),
+    min_context_slot: None,
+};
+
+/// 定期重发同一笔已签名交易，直到交易得出结果或 blockhash 过期
+///
+/// 是否结束由 ConfirmationTracker 判断，最终结果也由其发出
+pub struct Rebroadcaster {
+    rpc_client: Arc<RpcClient>,
+    broadcaster: Option<Arc<Broadcaster>>,
+    confirmations: Arc<ConfirmationTracker>,
+    interval: Duration,
+}
+
+impl Rebroadcaster {
+    pub fn new(
+        rpc_client: Arc<RpcClient>,
+        broadcaster: Option<Arc<Broadcaster>>,
+        confirmations: Arc<ConfirmationTracker>,
+        interval: Duration,
+    ) -> Arc<Self> {
+        if interval < MIN_INTERVAL {
+            warn!("重发间隔 {:?} 过小，改为 {:?}", interval, MIN_INTERVAL);
+        }
+        Arc::new(Self {
+            rpc_client,
+            broadcaster,
+            confirmations,
+            interval: interval.max(MIN_INTERVAL),
+        })
+    }
+
+    /// 交易需要在第一次重发(interval 之后)前登记到 ConfirmationTracker，提交成功后由 Dispatcher 登记
+    pub fn spawn(self: &Arc<Self>, tx: VersionedTransaction) {
+        let this = Arc::clone(self);
+        tokio::spawn(async move {
+            let signature = tx.signatures[0];
+            let mut resent = 0;
+            loop {
+                tokio::time::sleep(this.interval).await;
+                if !this.confirmations.is_pending(&signature) {
+                    break;
+                }
+
+                let result = match &this.broadcaster {
+                    // 重发不计入端点统计，保留首次发送时记录的端点
+                    Some(broadcaster) => broadcaster.resend(&tx, RESEND_CONFIG).await,
+                    None => this
+                        .rpc_client
+                        .send_transaction_with_config(&tx, RESEND_CONFIG)
+                        .await
+                        .map(|_| ())
+                        .map_err(anyhow::Error::from),
+                };
+                match result {
+                    Ok(_) => resent += 1,
+                    Err(e) => debug!("重发交易 {} 失败: {}", signature, e),
+                }
+            }
+            debug!("交易 {} 已结束跟踪，共重发 {} 次", signature, resent);
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_min_interval() {
+        let rebroadcaster = |interval: Duration| {
+            Rebroadcaster::new(
+                Arc::new(RpcClient::new("http://127.0.0.1:8899".to_string())),
+                None,
+                Arc::new(ConfirmationTracker::new(Duration::from_secs(60))),
+                interval,
+            )
+        };
+        assert_eq!(rebroadcaster(Duration::ZERO).interval, MIN_INTERVAL);
+        assert_eq!(
+            rebroadcaster(Duration::from_secs(2)).interval,
+            Duration::from_secs(2)
+        );
+    }
+}
@@ -0,0 +1,263 @@
+use crate::config::RelayConfig;
+use anyhow::{Result, anyhow};
+use base64::Engine as _;
+use rand::seq::IndexedRandom;
+use serde_json::{Value, json};
+use solana_sdk::{instruction::Instruction, pubkey::Pubkey, transaction::VersionedTransaction};
+use std::future::Future;
+use std::pin::Pin;
+use std::str::FromStr;
+use std::time::Duration;
+use tracing::debug;
+
+pub type SendFuture<'a> = Pin<Box<dyn Future<Output = Result<()>> + Send + 'a>>;
+
+/// 交易提交通道，如 RPC、Jito、第三方中继
+pub trait Sender: Send + Sync {
+    fn name(&self) -> &str;
+
+    /// 小费指令，None 表示该通道不需要小费
+    fn tip_instruction(&self, payer: &Pubkey) -> Option<Instruction>;
+
+    /// 每次提交支付的小费(lamports)，与 tip_instruction 的转账金额一致
+    fn tip_amount(&self) -> u64 {
+        0
+    }
+
+    /// 是否保留 compute unit price 指令(优先费)
+    ///
+    /// 按小费排序的通道不需要优先费，保留只会浪费 CU
+    fn priority_fee(&self) -> bool;
+
+    /// 覆盖优先费的 compute unit price(micro-lamports)，None 表示使用 Jupiter 返回的优先费
+    fn compute_unit_price(&self) -> Option<u64> {
+        None
+    }
+
+    /// 是否支持多笔交易原子执行(bundle)
+    fn supports_bundle(&self) -> bool;
+
+    /// 提交交易，txs 多于一笔时必须支持 bundle
+    fn send<'a>(&'a self, txs: &'a [VersionedTransaction], profit: u64) -> SendFuture<'a>;
+}
+
+/// 从小费账户中随机选择一个，生成转账指令
+pub fn tip_instruction(payer: &Pubkey, accounts: &[Pubkey], amount: u64) -> Option<Instruction> {
+    let account = accounts.choose(&mut rand::rng())?;
+    Some(solana_sdk::system_instruction::transfer(
+        payer, account, amount,
+    ))
+}
+
+pub fn encode_transactions(txs: &[VersionedTransaction]) -> Result<Vec<String>> {
+    txs.iter()
+        .map(|tx| Ok(base64::engine::general_purpose::STANDARD.encode(bincode::serialize(tx)?)))
+        .collect()
+}
+
+/// 通用 HTTP JSON-RPC 中继
+///
+/// 单笔交易调用 `method`(默认 sendTransaction)，配置了 `bundle_method` 时多笔交易以 bundle 方式提交
+pub struct HttpRelaySender {
+    name: String,
+    endpoint: String,
+    method: String,
+    bundle_method: Option<String>,
+    headers: reqwest::header::HeaderMap,
+    tip_accounts: Vec<Pubkey>,
+    tip_amount: u64,
+    priority_fee: bool,
+    client: reqwest::Client,
+    timeout: Duration,
+}
+
+impl HttpRelaySender {
+    pub fn new(config: &RelayConfig, client: reqwest::Client, timeout: Duration) -> Result<Self> {
+        let tip_accounts = config
+            .tip_accounts
+            .iter()
+            .map(|account| {
+                Pubkey::from_str(account)
+                    .map_err(|e| anyhow!("中继 {} 小费账户 {} 无效: {}", config.name, account, e))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        if tip_accounts.is_empty() && config.tip_amount > 0 {
+            return Err(anyhow!("中继 {} 未配置小费账户", config.name));
+        }
+
+        let mut headers = reqwest::header::HeaderMap::new();
+        for (key, value) in &config.headers {
+            headers.insert(
+                reqwest::header::HeaderName::from_str(key)?,
+                reqwest::header::HeaderValue::from_str(value)?,
+            );
+        }
+
+        Ok(Self {
+            name: config.name.clone(),
+            endpoint: config.endpoint.clone(),
+            method: config.method.clone(),
+            bundle_method: config.bundle_method.clone(),
+            headers,
+            tip_accounts,
+            tip_amount: config.tip_amount,
+            priority_fee: config.priority_fee,
+            client,
+            timeout,
+        })
+    }
+
+    fn request(&self, encoded_txs: Vec<String>) -> Result<Value> {
+        let (method, params) = if encoded_txs.len() == 1 {
+            (
+                &self.method,
+                json!([encoded_txs[0], {"encoding": "base64"}]),
+            )
+        } else {
+            let method = self
+                .bundle_method
+                .as_ref()
+                .ok_or_else(|| anyhow!("中继 {} 不支持 bundle", self.name))?;
+            (method, json!([encoded_txs, {"encoding": "base64"}]))
+        };
+
+        Ok(json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": method,
+            "params": params,
+        }))
+    }
+}
+
+impl Sender for HttpRelaySender {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn tip_instruction(&self, payer: &Pubkey) -> Option<Instruction> {
+        if self.tip_amount == 0 {
+            return None;
+        }
+        tip_instruction(payer, &self.tip_accounts, self.tip_amount)
+    }
+
+    fn tip_amount(&self) -> u64 {
+        self.tip_amount
+    }
+
+    fn priority_fee(&self) -> bool {
+        self.priority_fee
+    }
+
+    fn supports_bundle(&self) -> bool {
+        self.bundle_method.is_some()
+    }
+
+    fn send<'a>(&'a self, txs: &'a [VersionedTransaction], _profit: u64) -> SendFuture<'a> {
+        Box::pin(async move {
+            let request = self.request(encode_transactions(txs)?)?;
+
+            let res = self
+                .client
+                .post(&self.endpoint)
+                .headers(self.headers.clone())
+                .json(&request)
+                .timeout(self.timeout)
+                .send()
+                .await?
+                .error_for_status()?
+                .json::<Value>()
+                .await?;
+            debug!("[{}] response = {}", self.name, res);
+
+            if let Some(error) = res.get("error") {
+                return Err(anyhow!("❌ 中继 {} 提交失败: {}", self.name, error));
+            }
+
+            println!(
+                "✅ 已通过中继 {} 提交: {}",
+                self.name,
+                res.get("result").cloned().unwrap_or_default()
+            );
+            for (idx, tx) in txs.iter().enumerate() {
+                println!("✅ 交易{}: {:?}", idx + 1, tx.signatures[0]);
+            }
+            Ok(())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn relay_config(bundle_method: Option<&str>) -> RelayConfig {
+        RelayConfig {
+            name: "relay".to_string(),
+            endpoint: "http://127.0.0.1".to_string(),
+            method: "sendTransaction".to_string(),
+            bundle_method: bundle_method.map(str::to_string),
+            headers: HashMap::from([("Authorization".to_string(), "token".to_string())]),
+            tip_accounts: vec![Pubkey::new_unique().to_string()],
+            tip_amount: 1_000,
+            priority_fee: false,
+        }
+    }
+
+    #[test]
+    fn test_relay_request() {
+        let relay = HttpRelaySender::new(
+            &relay_config(None),
+            reqwest::Client::new(),
+            Duration::from_secs(1),
+        )
+        .unwrap();
+        assert!(!relay.supports_bundle());
+
+        let request = relay.request(vec!["tx1".to_string()]).unwrap();
+        assert_eq!(request["method"], "sendTransaction");
+        assert_eq!(request["params"][0], "tx1");
+
+        // 不支持 bundle 的中继无法提交多笔交易
+        assert!(
+            relay
+                .request(vec!["tx1".to_string(), "tx2".to_string()])
+                .is_err()
+        );
+
+        let relay = HttpRelaySender::new(
+            &relay_config(Some("sendBundle")),
+            reqwest::Client::new(),
+            Duration::from_secs(1),
+        )
+        .unwrap();
+        let request = relay
+            .request(vec!["tx1".to_string(), "tx2".to_string()])
+            .unwrap();
+        assert_eq!(request["method"], "sendBundle");
+        assert_eq!(request["params"][0], json!(["tx1", "tx2"]));
+    }
+
+    #[test]
+    fn test_relay_tip() {
+        let config = relay_config(None);
+        let relay =
+            HttpRelaySender::new(&config, reqwest::Client::new(), Duration::from_secs(1)).unwrap();
+        let payer = Pubkey::new_unique();
+        let ix = relay.tip_instruction(&payer).unwrap();
+        assert_eq!(relay.tip_amount(), 1_000);
+        assert_eq!(ix.accounts[0].pubkey, payer);
+        assert_eq!(ix.accounts[1].pubkey.to_string(), config.tip_accounts[0]);
+
+        // 配置了小费但没有小费账户
+        let config = RelayConfig {
+            tip_accounts: vec![],
+            ..config
+        };
+        assert!(
+            HttpRelaySender::new(&config, reqwest::Client::new(), Duration::from_secs(1)).is_err()
+        );
+    }
+}
@@ -0,0 +1,93 @@
+use crate::config::{SENDER_TPU, TpuConfig};
+use crate::sender::{SendFuture, Sender};
+use crate::{constants, util};
+use anyhow::{Result, anyhow};
+use solana_client::{
+    nonblocking::{rpc_client::RpcClient, tpu_client::TpuClient},
+    tpu_client::TpuClientConfig,
+};
+use solana_quic_client::{QuicConfig, QuicConnectionManager, QuicPool};
+use solana_sdk::{instruction::Instruction, pubkey::Pubkey, transaction::VersionedTransaction};
+use std::sync::Arc;
+use tokio::time::Instant;
+use tracing::debug;
+
+/// 绕过 RPC 转发，通过 QUIC 将交易直接发送到当前及后续 Leader 的 TPU 端口
+///
+/// Leader 由 leader schedule 及 cluster nodes 解析，fanout_slots 决定发送到后续多少个 slot 的 Leader
+pub struct TpuSender {
+    client: TpuClient<QuicPool, QuicConnectionManager, QuicConfig>,
+}
+
+impl TpuSender {
+    pub async fn new(
+        rpc_client: Arc<RpcClient>,
+        rpc_endpoint: &str,
+        config: &TpuConfig,
+    ) -> Result<Self> {
+        let websocket_url = match &config.websocket_url {
+            Some(url) => url.clone(),
+            None => util::websocket_url(rpc_endpoint)?,
+        };
+
+        let client = TpuClient::new(
+            "arbitrage-bot-tpu",
+            rpc_client,
+            &websocket_url,
+            TpuClientConfig {
+                fanout_slots: config.fanout_slots,
+            },
+        )
+        .await
+        .map_err(|e| anyhow!("TPU 客户端初始化失败: {}", e))?;
+
+        Ok(Self { client })
+    }
+}
+
+impl Sender for TpuSender {
+    fn name(&self) -> &str {
+        SENDER_TPU
+    }
+
+    fn tip_instruction(&self, _payer: &Pubkey) -> Option<Instruction> {
+        None
+    }
+
+    fn priority_fee(&self) -> bool {
+        true
+    }
+
+    fn supports_bundle(&self) -> bool {
+        false
+    }
+
+    fn send<'a>(&'a self, txs: &'a [VersionedTransaction], _profit: u64) -> SendFuture<'a> {
+        Box::pin(async move {
+            if txs.len() != 1 {
+                return Err(anyhow!("TPU 只能提交单笔交易，当前 {} 笔", txs.len()));
+            }
+
+            let wire_transaction = bincode::serialize(&txs[0])?;
+            if wire_transaction.len() > constants::TX_SIZE {
+                return Err(anyhow!(
+                    "❌ 当前交易数据大小 {} 超出Solana允许的单笔交易大小 1232 字节",
+                    wire_transaction.len()
+                ));
+            }
+
+            let start_time = Instant::now();
+            self.client
+                .try_send_wire_transaction(wire_transaction)
+                .await
+                .map_err(|e| anyhow!("❌ TPU 发送失败: {}", e))?;
+            debug!("TPU send elapsed_time: {:.4?}", start_time.elapsed());
+
+            println!(
+                "✅ 已通过 TPU 发送交易: https://solscan.io/tx/{}\n",
+                txs[0].signatures[0]
+            );
+            Ok(())
+        })
+    }
+}
@@ -0,0 +1,382 @@
+use crate::flashloan::FlashLoan;
+use anyhow::{Result, anyhow};
+use solana_sdk::{
+    compute_budget::ComputeBudgetInstruction, instruction::Instruction, pubkey::Pubkey,
+};
+use std::collections::HashSet;
+
+/// 一次套利的全部指令，按不同的交易布局进行组装
+#[derive(Debug, Clone)]
+pub struct ArbitrageInstructions {
+    /// 推进持久 nonce 的指令，必须是交易的第一条指令
+    pub advance_nonce: Option<Instruction>,
+    /// Jito 小费
+    pub tip: Option<Instruction>,
+    /// 第一笔 swap 的 compute budget 指令
+    pub compute_budget: Vec<Instruction>,
+    /// 第二笔 swap 的 compute budget 指令，只在拆分交易时使用
+    pub compute_budget_2: Vec<Instruction>,
+    /// 第一笔 swap: setup + swap + cleanup
+    pub leg1: Vec<Instruction>,
+    /// 第二笔 swap: setup + swap + cleanup
+    pub leg2: Vec<Instruction>,
+    pub memo: Instruction,
+    /// 利润检查，多个套利合并到同一个 bundle 时为 None，由 bundle 最后的结算交易统一检查
+    pub check_profit: Option<Instruction>,
+}
+
+impl ArbitrageInstructions {
+    /// 移除 compute unit price 指令，使用小费的通道不需要优先费
+    pub fn without_compute_unit_price(mut self) -> Self {
+        // https://github.com/solana-labs/solana/blob/master/sdk/src/compute_budget.rs#L25
+        let is_price_ix = |ix: &Instruction| {
+            ix.program_id == solana_sdk::compute_budget::id() && ix.data.first() == Some(&0x03)
+        };
+        self.compute_budget.retain(|ix| !is_price_ix(ix));
+        self.compute_budget_2.retain(|ix| !is_price_ix(ix));
+        self
+    }
+
+    /// 使用指定的 compute unit price 替换原有的优先费
+    pub fn with_compute_unit_price(self, micro_lamports: u64) -> Self {
+        let mut ixs = self.without_compute_unit_price();
+        let price_ix = ComputeBudgetInstruction::set_compute_unit_price(micro_lamports);
+        ixs.compute_budget.push(price_ix.clone());
+        ixs.compute_budget_2.push(price_ix);
+        ixs
+    }
+
+    /// 合并成一笔交易
+    ///
+    /// [advance nonce] + tip + compute budget + [borrow] + leg1 + leg2 + memo + [repay] + [check_profit]
+    pub fn merged(&self, flashloan: Option<&dyn FlashLoan>) -> Vec<Instruction> {
+        let mut ixs =
+            Vec::with_capacity(self.compute_budget.len() + self.leg1.len() + self.leg2.len() + 6);
+        ixs.extend(self.advance_nonce.iter().cloned());
+        ixs.extend(self.tip.iter().cloned());
+        ixs.extend(self.compute_budget.iter().cloned());
+
+        // 借款指令的位置即当前指令数量，最后一条还款指令位于 memo 之后
+        let borrow_index = ixs.len();
+        if let Some(flashloan) = flashloan {
+            let (borrow_count, repay_count) = flashloan.instruction_count();
+            let end_index = borrow_index
+                + borrow_count
+                + self.leg1.len()
+                + self.leg2.len()
+                + 1
+                + repay_count.saturating_sub(1);
+            ixs.extend(flashloan.borrow(borrow_index as u8, end_index as u8));
+        }
+        ixs.extend(self.leg1.iter().cloned());
+        ixs.extend(self.leg2.iter().cloned());
+        ixs.push(self.memo.clone());
+        if let Some(flashloan) = flashloan {
+            ixs.extend(flashloan.repay(borrow_index as u8));
+        }
+        ixs.extend(self.check_profit.iter().cloned());
+        ixs
+    }
+
+    /// 拆分成两笔交易，必须通过 Jito bundle 原子提交
+    ///
+    /// 交易1: [advance nonce] + compute budget + leg1
+    /// 交易2: compute budget + leg2 + memo + [check_profit] + tip
+    ///
+    /// 交易1 推进 nonce 后 nonce 值随之改变，交易2 需要使用最新的 blockhash
+    ///
+    /// 闪电贷的借款与还款指令必须位于同一笔交易中（Kamino 与 MarginFi 都通过
+    /// Instructions Sysvar 在当前交易内查找对应的还款指令，bundle 中的其它交易不可见），
+    /// 借款放在交易1、还款放在交易2 的布局在链上必定失败，因此使用闪电贷时不拆分，
+    /// 直接返回错误由调用方放弃该套利
+    pub fn split(
+        &self,
+        flashloan: Option<&dyn FlashLoan>,
+    ) -> Result<(Vec<Instruction>, Vec<Instruction>)> {
+        if flashloan.is_some() {
+            return Err(anyhow!(
+                "使用闪电贷的交易不支持拆分(借款与还款指令必须位于同一笔交易中)"
+            ));
+        }
+
+        let mut ixs1 = Vec::with_capacity(self.compute_budget.len() + self.leg1.len() + 1);
+        ixs1.extend(self.advance_nonce.iter().cloned());
+        ixs1.extend(self.compute_budget.iter().cloned());
+        ixs1.extend(self.leg1.iter().cloned());
+
+        let mut ixs2 = Vec::with_capacity(self.compute_budget_2.len() + self.leg2.len() + 3);
+        ixs2.extend(self.compute_budget_2.iter().cloned());
+        ixs2.extend(self.leg2.iter().cloned());
+        ixs2.push(self.memo.clone());
+        ixs2.extend(self.check_profit.iter().cloned());
+        // 小费放在最后一笔交易，bundle 中任意交易失败都不会支付小费
+        ixs2.extend(self.tip.iter().cloned());
+
+        Ok((ixs1, ixs2))
+    }
+}
+
+/// 指令中的可写账户，excluded 中的账户(用户自身账户)不计算在内
+pub fn write_locks(ixs: &[Instruction], excluded: &HashSet<Pubkey>) -> HashSet<Pubkey> {
+    ixs.iter()
+        .flat_map(|ix| ix.accounts.iter())
+        .filter(|meta| meta.is_writable && !excluded.contains(&meta.pubkey))
+        .map(|meta| meta.pubkey)
+        .collect()
+}
+
+/// 等待合并到同一个 bundle 的套利
+#[derive(Debug)]
+pub struct BatchCandidate<'a> {
+    pub profit: u64,
+    /// 占用的交易数量
+    pub tx_count: usize,
+    pub write_set: &'a HashSet<Pubkey>,
+}
+
+/// 按利润从高到低挑选可以合并到同一个 bundle 的套利
+///
+/// 与已选套利存在写锁冲突，或超出 bundle 交易数量上限的套利将被跳过。
+/// 返回值按利润从高到低排列，且至少包含两个套利，否则合并没有意义
+pub fn pack_bundle(candidates: &[BatchCandidate], max_txs: usize) -> Vec<usize> {
+    let mut order: Vec<usize> = (0..candidates.len()).collect();
+    order.sort_by_key(|&idx| std::cmp::Reverse(candidates[idx].profit));
+
+    let mut selected = Vec::new();
+    let mut locked: HashSet<Pubkey> = HashSet::new();
+    let mut tx_count = 0;
+    for idx in order {
+        let candidate = &candidates[idx];
+        if candidate.tx_count > max_txs - tx_count {
+            continue;
+        }
+        if !candidate.write_set.is_disjoint(&locked) {
+            continue;
+        }
+
+        tx_count += candidate.tx_count;
+        locked.extend(candidate.write_set.iter().copied());
+        selected.push(idx);
+    }
+
+    if selected.len() < 2 {
+        selected.clear();
+    }
+    selected
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::flashloan::FlashLoanError;
+    use solana_sdk::instruction::AccountMeta;
+
+    fn ix(tag: u8) -> Instruction {
+        Instruction {
+            program_id: Pubkey::new_unique(),
+            accounts: vec![],
+            data: vec![tag],
+        }
+    }
+
+    struct FakeFlashLoan;
+    impl FlashLoan for FakeFlashLoan {
+        fn borrow(&self, borrow_index: u8, end_index: u8) -> Vec<Instruction> {
+            vec![Instruction {
+                program_id: Pubkey::default(),
+                accounts: vec![],
+                data: vec![0xb0, borrow_index, end_index],
+            }]
+        }
+        fn repay(&self, borrow_index: u8) -> Vec<Instruction> {
+            vec![Instruction {
+                program_id: Pubkey::default(),
+                accounts: vec![],
+                data: vec![0xe0, borrow_index],
+            }]
+        }
+        fn instruction_count(&self) -> (usize, usize) {
+            (1, 1)
+        }
+        fn available_liquidity(&self) -> u64 {
+            u64::MAX
+        }
+        fn check(&self) -> Result<(), FlashLoanError> {
+            Ok(())
+        }
+        fn fee(&self) -> u64 {
+            0
+        }
+    }
+
+    fn instructions() -> ArbitrageInstructions {
+        ArbitrageInstructions {
+            advance_nonce: None,
+            tip: Some(ix(1)),
+            compute_budget: vec![ix(2), ix(3)],
+            compute_budget_2: vec![ix(4)],
+            leg1: vec![ix(5), ix(6)],
+            leg2: vec![ix(7)],
+            memo: ix(8),
+            check_profit: Some(ix(9)),
+        }
+    }
+
+    fn tags(ixs: &[Instruction]) -> Vec<u8> {
+        ixs.iter().map(|ix| ix.data[0]).collect()
+    }
+
+    #[test]
+    fn test_merged_layout() {
+        let plan = instructions();
+        assert_eq!(tags(&plan.merged(None)), vec![1, 2, 3, 5, 6, 7, 8, 9]);
+
+        let ixs = plan.merged(Some(&FakeFlashLoan));
+        assert_eq!(tags(&ixs), vec![1, 2, 3, 0xb0, 5, 6, 7, 8, 0xe0, 9]);
+        // 借款、还款指令记录的位置与实际位置一致
+        assert_eq!(ixs[3].data[1..], [3, 8]);
+        assert_eq!(ixs[8].data[1], 3);
+
+        // nonce 推进指令位于第一条，借款指令位置随之后移
+        let plan = ArbitrageInstructions {
+            advance_nonce: Some(ix(0)),
+            ..plan
+        };
+        let ixs = plan.merged(Some(&FakeFlashLoan));
+        assert_eq!(tags(&ixs), vec![0, 1, 2, 3, 0xb0, 5, 6, 7, 8, 0xe0, 9]);
+        assert_eq!(ixs[4].data[1..], [4, 9]);
+        assert_eq!(ixs[9].data[1], 4);
+
+        // 合并到 bundle 的套利不带利润检查及小费
+        let plan = ArbitrageInstructions {
+            advance_nonce: None,
+            tip: None,
+            check_profit: None,
+            ..plan
+        };
+        assert_eq!(tags(&plan.merged(None)), vec![2, 3, 5, 6, 7, 8]);
+        let (ixs1, ixs2) = plan.split(None).unwrap();
+        assert_eq!(tags(&ixs1), vec![2, 3, 5, 6]);
+        assert_eq!(tags(&ixs2), vec![4, 7, 8]);
+    }
+
+    #[test]
+    fn test_without_compute_unit_price() {
+        let compute_budget_ix = |tag: u8| Instruction {
+            program_id: solana_sdk::compute_budget::id(),
+            accounts: vec![],
+            data: vec![tag],
+        };
+        let plan = ArbitrageInstructions {
+            compute_budget: vec![compute_budget_ix(0x02), compute_budget_ix(0x03)],
+            compute_budget_2: vec![compute_budget_ix(0x03)],
+            ..instructions()
+        }
+        .without_compute_unit_price();
+        assert_eq!(tags(&plan.compute_budget), vec![0x02]);
+        assert!(plan.compute_budget_2.is_empty());
+
+        let plan = plan.with_compute_unit_price(1_000);
+        assert_eq!(tags(&plan.compute_budget), vec![0x02, 0x03]);
+        assert_eq!(
+            plan.compute_budget_2,
+            vec![ComputeBudgetInstruction::set_compute_unit_price(1_000)]
+        );
+    }
+
+    #[test]
+    fn test_split_layout() {
+        let plan = instructions();
+        let (ixs1, ixs2) = plan.split(None).unwrap();
+        assert_eq!(tags(&ixs1), vec![2, 3, 5, 6]);
+        assert_eq!(tags(&ixs2), vec![4, 7, 8, 9, 1]);
+
+        assert!(plan.split(Some(&FakeFlashLoan)).is_err());
+
+        let plan = ArbitrageInstructions {
+            advance_nonce: Some(ix(0)),
+            ..plan
+        };
+        let (ixs1, ixs2) = plan.split(None).unwrap();
+        assert_eq!(tags(&ixs1), vec![0, 2, 3, 5, 6]);
+        assert_eq!(tags(&ixs2), vec![4, 7, 8, 9, 1]);
+    }
+
+    #[test]
+    fn test_write_locks() {
+        let user = Pubkey::new_unique();
+        let pool = Pubkey::new_unique();
+        let oracle = Pubkey::new_unique();
+        let ix = Instruction {
+            program_id: Pubkey::new_unique(),
+            accounts: vec![
+                AccountMeta::new(user, true),
+                AccountMeta::new(pool, false),
+                AccountMeta::new_readonly(oracle, false),
+            ],
+            data: vec![],
+        };
+
+        let locks = write_locks(&[ix], &HashSet::from([user]));
+        assert_eq!(locks, HashSet::from([pool]));
+    }
+
+    #[test]
+    fn test_pack_bundle() {
+        let pool_a = Pubkey::new_unique();
+        let pool_b = Pubkey::new_unique();
+        let pool_c = Pubkey::new_unique();
+        let set_a = HashSet::from([pool_a]);
+        let set_ab = HashSet::from([pool_a, pool_b]);
+        let set_c = HashSet::from([pool_c]);
+        let set_b = HashSet::from([pool_b]);
+
+        let candidates = [
+            BatchCandidate {
+                profit: 100,
+                tx_count: 1,
+                write_set: &set_a,
+            },
+            // 与利润更高的套利冲突
+            BatchCandidate {
+                profit: 50,
+                tx_count: 1,
+                write_set: &set_ab,
+            },
+            BatchCandidate {
+                profit: 80,
+                tx_count: 2,
+                write_set: &set_c,
+            },
+            // 超出交易数量上限
+            BatchCandidate {
+                profit: 70,
+                tx_count: 2,
+                write_set: &set_b,
+            },
+            BatchCandidate {
+                profit: 10,
+                tx_count: 1,
+                write_set: &set_b,
+            },
+        ];
+        assert_eq!(pack_bundle(&candidates, 4), vec![0, 2, 4]);
+
+        // 只有一个套利时不合并
+        assert!(pack_bundle(&candidates[..1], 5).is_empty());
+        // 无法生成交易的套利
+        let candidates = [
+            BatchCandidate {
+                profit: 100,
+                tx_count: usize::MAX,
+                write_set: &set_a,
+            },
+            BatchCandidate {
+                profit: 10,
+                tx_count: 1,
+                write_set: &set_b,
+            },
+        ];
+        assert!(pack_bundle(&candidates, 5).is_empty());
+    }
+}
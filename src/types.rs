@@ -1,3 +1,4 @@
+use crate::flashloan::registry::Selected;
 use base64::{Engine as _, engine::general_purpose};
 use serde::ser::{SerializeStruct, Serializer};
 use serde::{Deserialize, Serialize};
@@ -199,4 +200,8 @@ impl From<EncodedAccountMeta> for AccountMeta {
 pub struct SwapData {
     pub data1: QuoteResponse,
     pub data2: QuoteResponse,
+    /// 报价计算出的预期利润，已扣除闪电贷费用
+    pub profit: u64,
+    /// 选定的闪电贷来源
+    pub flashloan: Option<Selected>,
 }
@@ -15,7 +15,7 @@ use std::str::FromStr;
 use std::time::Duration;
 use tracing::warn;
 
-pub async fn get_jito_tip_fee_account() -> Result<Pubkey> {
+pub fn get_jito_tip_fee_account() -> Result<Pubkey> {
     // let tip_account_str = jito_sdk.get_random_tip_account().await?;
     // let _tip_account = Pubkey::from_str(&tip_account_str)?;
     // Ok(tip_account)
@@ -166,6 +166,38 @@ pub fn parse_ipv4_string(ip_str: &str) -> Result<Vec<Ipv4Addr>> {
         .map_err(|e| anyhow!("无效的IPv4地址: {}", e))
 }
 
+/// 根据 RPC 地址推导 WebSocket 地址
+///
+/// 与 solana-cli 一致：https -> wss、http -> ws，地址中指定了端口时使用端口 + 1(验证者的 pubsub 端口)
+pub fn websocket_url(rpc_endpoint: &str) -> Result<String> {
+    let (scheme, rest) = if let Some(rest) = rpc_endpoint.strip_prefix("https://") {
+        ("wss", rest)
+    } else if let Some(rest) = rpc_endpoint.strip_prefix("http://") {
+        ("ws", rest)
+    } else {
+        return Err(anyhow!(
+            "无法根据 RPC 地址 {} 推导 WebSocket 地址",
+            rpc_endpoint
+        ));
+    };
+
+    let (authority, path) = rest.split_at(rest.find(['/', '?']).unwrap_or(rest.len()));
+    let authority = match authority.rsplit_once(':') {
+        Some((host, port))
+            if !host.is_empty() && !port.is_empty() && port.bytes().all(|b| b.is_ascii_digit()) =>
+        {
+            let port = port
+                .parse::<u16>()
+                .ok()
+                .and_then(|port| port.checked_add(1))
+                .ok_or_else(|| anyhow!("RPC 地址 {} 的端口无效", rpc_endpoint))?;
+            format!("{}:{}", host, port)
+        }
+        _ => authority.to_string(),
+    };
+    Ok(format!("{}://{}{}", scheme, authority, path))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -176,6 +208,29 @@ mod tests {
     use std::io::Write;
     use tempfile::NamedTempFile;
 
+    #[test]
+    fn test_websocket_url() {
+        assert_eq!(
+            websocket_url("https://api.mainnet-beta.solana.com").unwrap(),
+            "wss://api.mainnet-beta.solana.com"
+        );
+        // pubsub 端口为 RPC 端口 + 1
+        assert_eq!(
+            websocket_url("http://127.0.0.1:8899").unwrap(),
+            "ws://127.0.0.1:8900"
+        );
+        assert_eq!(
+            websocket_url("https://rpc.example.com:8443/?api-key=xxx").unwrap(),
+            "wss://rpc.example.com:8444/?api-key=xxx"
+        );
+        assert_eq!(
+            websocket_url("http://[::1]:8899").unwrap(),
+            "ws://[::1]:8900"
+        );
+        assert!(websocket_url("http://127.0.0.1:65535").is_err());
+        assert!(websocket_url("127.0.0.1:8899").is_err());
+    }
+
     #[test]
     fn test_find_set_compute_unit_limit_ix() {
         // SetComputeUnitLimit 指令 (0x02 + 小端 u32 = 1,000,000 = 0x40420f00)